@@ -1,7 +1,11 @@
 use egui::{Color32, Pos2, Rect, Vec2};
 use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::fonts::{font_chain, DEFAULT_FAMILY};
+use crate::text_layout::{layout_text, TextLayout};
+
 pub type AnnotationId = u64;
+pub type GroupId = u64;
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Tool {
@@ -11,21 +15,176 @@ pub enum Tool {
     Text,
     Rectangle,
     Ellipse,
+    Freehand,
+    Highlighter,
+    Redact,
+    Step,
+    Callout,
+    Polygon,
+    Spotlight,
+    Magnifier,
+    Stamp,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ArrowHead {
+    None,
+    Open,
+    #[default]
+    Filled,
+    Dot,
+    Double,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum ArrowCurve {
+    Quadratic { control: Point },
+    Cubic { control_a: Point, control_b: Point },
+}
+
+impl ArrowCurve {
+    fn to_cubic(self, from: Point, to: Point) -> Self {
+        match self {
+            Self::Quadratic { control } => Self::Cubic {
+                control_a: lerp_point(from, control, 2.0 / 3.0),
+                control_b: lerp_point(to, control, 2.0 / 3.0),
+            },
+            cubic @ Self::Cubic { .. } => cubic,
+        }
+    }
+
+    fn point_at(self, from: Point, to: Point, t: f32) -> Point {
+        let u = 1.0 - t;
+        match self {
+            Self::Quadratic { control } => Point::new(
+                u * u * from.x + 2.0 * u * t * control.x + t * t * to.x,
+                u * u * from.y + 2.0 * u * t * control.y + t * t * to.y,
+            ),
+            Self::Cubic {
+                control_a,
+                control_b,
+            } => Point::new(
+                u * u * u * from.x
+                    + 3.0 * u * u * t * control_a.x
+                    + 3.0 * u * t * t * control_b.x
+                    + t * t * t * to.x,
+                u * u * u * from.y
+                    + 3.0 * u * u * t * control_a.y
+                    + 3.0 * u * t * t * control_b.y
+                    + t * t * t * to.y,
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RedactMode {
+    Blur,
+    Pixelate,
+    Solid,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SpotlightShape {
+    Rectangle,
+    Ellipse,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum StampIcon {
+    #[default]
+    Check,
+    Cross,
+    Warning,
+    Info,
+    Question,
+    Cursor,
+    ThumbsUp,
+    ThumbsDown,
+}
+
+impl StampIcon {
+    pub const ALL: [StampIcon; 8] = [
+        StampIcon::Check,
+        StampIcon::Cross,
+        StampIcon::Warning,
+        StampIcon::Info,
+        StampIcon::Question,
+        StampIcon::Cursor,
+        StampIcon::ThumbsUp,
+        StampIcon::ThumbsDown,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StampIcon::Check => "Check",
+            StampIcon::Cross => "Cross",
+            StampIcon::Warning => "Warning",
+            StampIcon::Info => "Info",
+            StampIcon::Question => "Question",
+            StampIcon::Cursor => "Cursor",
+            StampIcon::ThumbsUp => "Thumbs up",
+            StampIcon::ThumbsDown => "Thumbs down",
+        }
+    }
 }
 
+pub const DEFAULT_STAMP_SIZE: f32 = 48.0;
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
-pub enum StrokeWidth {
-    Thin,
-    Medium,
-    Thick,
+pub enum LoupeShape {
+    Circle,
+    RoundedRect,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StrokeWidth(f32);
+
 impl StrokeWidth {
+    pub const MIN: f32 = 0.5;
+    pub const MAX: f32 = 40.0;
+    pub const THIN: Self = Self(1.5);
+    pub const MEDIUM: Self = Self(3.0);
+    pub const THICK: Self = Self(5.0);
+
+    pub fn from_px(px: f32) -> Self {
+        if px.is_nan() {
+            return Self::MEDIUM;
+        }
+        Self(px.clamp(Self::MIN, Self::MAX))
+    }
+
     pub fn px(self) -> f32 {
+        self.0
+    }
+
+    pub fn highlighter_px(self) -> f32 {
+        self.px() * 6.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum StrokeStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl StrokeStyle {
+    pub fn dash_pattern(self, width: f32) -> Option<[f32; 2]> {
         match self {
-            Self::Thin => 1.5,
-            Self::Medium => 3.0,
-            Self::Thick => 5.0,
+            Self::Solid => None,
+            Self::Dashed => Some([width * 3.0, width * 2.0]),
+            Self::Dotted => Some([0.0, width * 2.0]),
         }
     }
 }
@@ -115,10 +274,182 @@ impl<'de> Deserialize<'de> for TextSize {
     }
 }
 
+impl Serialize for StrokeWidth {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_f32(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for StrokeWidth {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct StrokeWidthVisitor;
+
+        impl<'de> Visitor<'de> for StrokeWidthVisitor {
+            type Value = StrokeWidth;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("line width in pixels or legacy values Thin/Medium/Thick")
+            }
+
+            fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(StrokeWidth::from_px(value as f32))
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(StrokeWidth::from_px(value.min(u32::MAX as u64) as f32))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(StrokeWidth::from_px(value as f32))
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                match value {
+                    "Thin" => Ok(StrokeWidth::THIN),
+                    "Medium" => Ok(StrokeWidth::MEDIUM),
+                    "Thick" => Ok(StrokeWidth::THICK),
+                    other => {
+                        let parsed = other.parse::<f32>().map_err(|_| {
+                            E::custom(format!(
+                                "invalid line width '{other}', expected Thin/Medium/Thick or number"
+                            ))
+                        })?;
+                        Ok(StrokeWidth::from_px(parsed))
+                    }
+                }
+            }
+        }
+
+        deserializer.deserialize_any(StrokeWidthVisitor)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextFont {
+    pub family: String,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl Default for TextFont {
+    fn default() -> Self {
+        Self {
+            family: DEFAULT_FAMILY.to_string(),
+            bold: false,
+            italic: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextEffects {
+    pub outline: bool,
+    pub outline_color: [u8; 4],
+    pub outline_width: f32,
+    pub shadow: bool,
+    pub background: bool,
+    pub background_color: [u8; 4],
+}
+
+impl Default for TextEffects {
+    fn default() -> Self {
+        Self {
+            outline: false,
+            outline_color: [255, 255, 255, 255],
+            outline_width: 2.0,
+            shadow: false,
+            background: false,
+            background_color: [255, 255, 255, 230],
+        }
+    }
+}
+
+pub const TEXT_SHADOW_COLOR: [u8; 4] = [0, 0, 0, 140];
+
+impl TextEffects {
+    pub const MAX_OUTLINE_WIDTH: f32 = 8.0;
+
+    pub fn shadow_offset(size: TextSize) -> f32 {
+        (size.points() * 0.08).max(1.0)
+    }
+
+    pub fn plate_padding(size: TextSize) -> f32 {
+        size.points() * 0.3
+    }
+
+    pub fn outline_px(&self) -> f32 {
+        self.outline_width.clamp(0.0, Self::MAX_OUTLINE_WIDTH)
+    }
+
+    pub fn margin(&self, size: TextSize) -> f32 {
+        let mut margin: f32 = 0.0;
+        if self.outline {
+            margin = margin.max(self.outline_px());
+        }
+        if self.shadow {
+            margin = margin.max(Self::shadow_offset(size));
+        }
+        if self.background {
+            margin = margin.max(Self::plate_padding(size));
+        }
+        margin
+    }
+
+    pub fn outline_offsets(&self, scale: f32) -> Vec<Vec2> {
+        let width = self.outline_px() * scale;
+        if !self.outline || width <= 0.0 {
+            return Vec::new();
+        }
+        let rings = width.ceil().max(1.0) as usize;
+        let mut offsets = Vec::new();
+        for ring in 1..=rings {
+            let radius = width * ring as f32 / rings as f32;
+            let steps = (radius * 8.0).ceil().clamp(8.0, 48.0) as usize;
+            for step in 0..steps {
+                let angle = step as f32 / steps as f32 * std::f32::consts::TAU;
+                offsets.push(Vec2::angled(angle) * radius);
+            }
+        }
+        offsets
+    }
+}
+
+pub fn default_line_spacing() -> f32 {
+    1.0
+}
+
 fn default_text_size() -> TextSize {
     TextSize::M
 }
 
+pub fn default_spotlight_dim() -> f32 {
+    0.6
+}
+
+fn default_true() -> bool {
+    true
+}
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Point {
     pub x: f32,
@@ -171,6 +502,52 @@ impl RectData {
             max: Point::from_pos2(value.max),
         }
     }
+
+    pub fn handles(self) -> Vec<(Handle, Point)> {
+        let r = self.to_rect();
+        let c = r.center();
+        vec![
+            (Handle::TopLeft, Point::from_pos2(r.left_top())),
+            (Handle::Top, Point::new(c.x, r.top())),
+            (Handle::TopRight, Point::from_pos2(r.right_top())),
+            (Handle::Right, Point::new(r.right(), c.y)),
+            (Handle::BottomRight, Point::from_pos2(r.right_bottom())),
+            (Handle::Bottom, Point::new(c.x, r.bottom())),
+            (Handle::BottomLeft, Point::from_pos2(r.left_bottom())),
+            (Handle::Left, Point::new(r.left(), c.y)),
+        ]
+    }
+
+    pub fn resize_from_handle(&mut self, handle: Handle, to: Point, keep_square: bool) {
+        let mut r = self.to_rect();
+        match handle {
+            Handle::TopLeft => r.min = to.to_pos2(),
+            Handle::Top => r.min.y = to.y,
+            Handle::TopRight => {
+                r.min.y = to.y;
+                r.max.x = to.x;
+            }
+            Handle::Right => r.max.x = to.x,
+            Handle::BottomRight => r.max = to.to_pos2(),
+            Handle::Bottom => r.max.y = to.y,
+            Handle::BottomLeft => {
+                r.min.x = to.x;
+                r.max.y = to.y;
+            }
+            Handle::Left => r.min.x = to.x,
+            _ => {}
+        }
+
+        if keep_square {
+            let mut size = r.size();
+            let side = size.x.abs().max(size.y.abs());
+            size.x = side * size.x.signum().max(1.0);
+            size.y = side * size.y.signum().max(1.0);
+            r.max = r.min + size;
+        }
+
+        *self = RectData::from_rect(r).normalize();
+    }
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -185,6 +562,15 @@ pub enum Handle {
     Left,
     ArrowFrom,
     ArrowTo,
+    ArrowControl,
+    ArrowControlB,
+    CalloutTail,
+    Vertex(usize),
+    SourceTopLeft,
+    SourceBottomRight,
+    CornerRadius,
+    TextWidth,
+    Rotate,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -193,6 +579,12 @@ pub struct Annotation {
     pub kind: AnnotationKind,
     pub color: [u8; 4],
     pub stroke_width: StrokeWidth,
+    #[serde(default)]
+    pub stroke_style: StrokeStyle,
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default)]
+    pub group: Option<GroupId>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -200,6 +592,10 @@ pub enum AnnotationKind {
     Arrow {
         from: Point,
         to: Point,
+        #[serde(default)]
+        head: ArrowHead,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        curve: Option<ArrowCurve>,
     },
     ArrowWithText {
         from: Point,
@@ -207,17 +603,88 @@ pub enum AnnotationKind {
         text: String,
         #[serde(default = "default_text_size")]
         size: TextSize,
+        #[serde(default)]
+        head: ArrowHead,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        curve: Option<ArrowCurve>,
+        #[serde(default)]
+        effects: TextEffects,
+        #[serde(default)]
+        font: TextFont,
     },
     Text {
         pos: Point,
         content: String,
         size: TextSize,
+        #[serde(default)]
+        effects: TextEffects,
+        #[serde(default)]
+        font: TextFont,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        wrap_width: Option<f32>,
+        #[serde(default)]
+        align: TextAlign,
+        #[serde(default = "default_line_spacing")]
+        line_spacing: f32,
     },
     Rectangle {
         rect: RectData,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fill: Option<[u8; 4]>,
+        #[serde(default)]
+        corner_radius: f32,
     },
     Ellipse {
         rect: RectData,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fill: Option<[u8; 4]>,
+    },
+    Freehand {
+        points: Vec<Point>,
+    },
+    Highlighter {
+        points: Vec<Point>,
+    },
+    Redact {
+        rect: RectData,
+        mode: RedactMode,
+    },
+    Step {
+        center: Point,
+        number: u32,
+        #[serde(default = "default_text_size")]
+        size: TextSize,
+    },
+    Callout {
+        rect: RectData,
+        tail: Point,
+        text: String,
+        size: TextSize,
+    },
+    Polygon {
+        points: Vec<Point>,
+        closed: bool,
+        #[serde(default)]
+        filled: bool,
+    },
+    Spotlight {
+        rect: RectData,
+        shape: SpotlightShape,
+        #[serde(default = "default_spotlight_dim")]
+        dim: f32,
+        #[serde(default)]
+        desaturate: bool,
+    },
+    Magnifier {
+        source: RectData,
+        target: RectData,
+        shape: LoupeShape,
+        #[serde(default = "default_true")]
+        connector: bool,
+    },
+    Stamp {
+        rect: RectData,
+        icon: StampIcon,
     },
 }
 
@@ -226,48 +693,177 @@ impl Annotation {
         Color32::from_rgba_unmultiplied(self.color[0], self.color[1], self.color[2], self.color[3])
     }
 
+    pub fn line_width(&self) -> f32 {
+        match self.kind {
+            AnnotationKind::Highlighter { .. } => self.stroke_width.highlighter_px(),
+            _ => self.stroke_width.px(),
+        }
+    }
+
+    pub fn supports_stroke_style(&self) -> bool {
+        matches!(
+            self.kind,
+            AnnotationKind::Arrow { .. }
+                | AnnotationKind::ArrowWithText { .. }
+                | AnnotationKind::Rectangle { .. }
+                | AnnotationKind::Ellipse { .. }
+                | AnnotationKind::Polygon { .. }
+                | AnnotationKind::Freehand { .. }
+        )
+    }
+
+    pub fn polygon_fill_color(&self) -> [u8; 4] {
+        let [r, g, b, a] = self.color;
+        [r, g, b, (a as f32 * POLYGON_FILL_ALPHA).round() as u8]
+    }
+
+    pub fn contrast_text_color(&self) -> [u8; 4] {
+        let [r, g, b, _] = self.color;
+        let luminance = 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32;
+        if luminance > 160.0 {
+            [0x1A, 0x20, 0x2C, 0xFF]
+        } else {
+            [0xFF, 0xFF, 0xFF, 0xFF]
+        }
+    }
+
+    pub fn can_rotate(&self) -> bool {
+        matches!(
+            self.kind,
+            AnnotationKind::Text { .. }
+                | AnnotationKind::Rectangle { .. }
+                | AnnotationKind::Ellipse { .. }
+                | AnnotationKind::Callout { .. }
+                | AnnotationKind::Polygon { .. }
+                | AnnotationKind::Stamp { .. }
+        )
+    }
+
+    pub fn turn(&self) -> Turn {
+        if self.rotation == 0.0 {
+            return Turn::NONE;
+        }
+        Turn {
+            center: self.local_bounds().center(),
+            angle: self.rotation,
+        }
+    }
+
     pub fn bounds(&self) -> Rect {
+        let local = self.local_bounds();
+        if self.rotation == 0.0 {
+            return local;
+        }
+        let turn = Turn {
+            center: local.center(),
+            angle: self.rotation,
+        };
+        let corners = [
+            local.left_top(),
+            local.right_top(),
+            local.right_bottom(),
+            local.left_bottom(),
+        ];
+        Rect::from_points(&corners.map(|corner| turn.apply(corner)))
+    }
+
+    pub fn local_bounds(&self) -> Rect {
         match &self.kind {
-            AnnotationKind::Arrow { from, to } => {
-                Rect::from_two_pos(from.to_pos2(), to.to_pos2()).expand(8.0)
-            }
+            AnnotationKind::Arrow {
+                from, to, curve, ..
+            } => polyline_rect(&arrow_polyline(*from, *to, *curve)).expand(8.0),
             AnnotationKind::ArrowWithText {
                 from,
                 to,
-                text,
                 size,
+                curve,
+                effects,
+                ..
             } => {
-                let arrow = Rect::from_two_pos(from.to_pos2(), to.to_pos2()).expand(8.0);
-                let text_w = (text.chars().count().max(1) as f32 * size.points() * 0.55).max(16.0);
-                let text_h = size.points() * 1.3;
-                let text_anchor = Point::new(from.x + 8.0, from.y - size.points() * 0.8);
-                let text_rect =
-                    Rect::from_min_size(text_anchor.to_pos2(), Vec2::new(text_w, text_h))
-                        .expand(4.0);
-                arrow.union(text_rect)
+                let arrow = polyline_rect(&arrow_polyline(*from, *to, *curve)).expand(8.0);
+                match self.text_box() {
+                    Some(text_box) => arrow.union(text_box.expand(effects.margin(*size))),
+                    None => arrow,
+                }
+            }
+            AnnotationKind::Text {
+                pos, size, effects, ..
+            } => self
+                .text_box()
+                .unwrap_or(Rect::from_min_size(pos.to_pos2(), Vec2::ZERO))
+                .expand(effects.margin(*size)),
+            AnnotationKind::Rectangle { rect, .. }
+            | AnnotationKind::Ellipse { rect, .. }
+            | AnnotationKind::Redact { rect, .. }
+            | AnnotationKind::Spotlight { rect, .. }
+            | AnnotationKind::Stamp { rect, .. } => rect.to_rect().expand(4.0),
+            AnnotationKind::Step { center, size, .. } => {
+                Rect::from_center_size(center.to_pos2(), Vec2::splat(step_radius(*size) * 2.0))
+                    .expand(2.0)
+            }
+            AnnotationKind::Callout { rect, tail, .. } => {
+                let mut r = rect.to_rect();
+                r.extend_with(tail.to_pos2());
+                r.expand(4.0)
             }
-            AnnotationKind::Text { pos, content, size } => {
-                // Conservative estimate for hit-testing and selection boxes.
-                let width = (content.chars().count().max(1) as f32 * size.points() * 0.6).max(20.0);
-                let height = size.points() * 1.4;
-                Rect::from_min_size(pos.to_pos2(), Vec2::new(width, height))
+            AnnotationKind::Freehand { points } | AnnotationKind::Highlighter { points } => {
+                polyline_rect(points).expand(self.line_width() * 0.5 + 4.0)
             }
-            AnnotationKind::Rectangle { rect } | AnnotationKind::Ellipse { rect } => {
-                rect.to_rect().expand(4.0)
+            AnnotationKind::Polygon { points, .. } => {
+                polyline_rect(points).expand(self.line_width() * 0.5 + 4.0)
             }
+            AnnotationKind::Magnifier { source, target, .. } => source
+                .to_rect()
+                .union(target.to_rect())
+                .expand(self.line_width() * 0.5 + 4.0),
         }
     }
 
     pub fn contains(&self, point: Point, tolerance: f32) -> bool {
+        let p = self.turn().invert(point.to_pos2());
+        self.local_contains(Point::from_pos2(p), tolerance)
+    }
+
+    fn local_contains(&self, point: Point, tolerance: f32) -> bool {
         let p = point.to_pos2();
         match &self.kind {
-            AnnotationKind::Arrow { from, to } | AnnotationKind::ArrowWithText { from, to, .. } => {
-                distance_to_segment(p, from.to_pos2(), to.to_pos2())
+            AnnotationKind::Arrow {
+                from, to, curve, ..
+            } => {
+                distance_to_polyline(p, &arrow_polyline(*from, *to, *curve))
+                    <= tolerance + self.stroke_width.px()
+            }
+            AnnotationKind::ArrowWithText {
+                from, to, curve, ..
+            } => {
+                distance_to_polyline(p, &arrow_polyline(*from, *to, *curve))
                     <= tolerance + self.stroke_width.px()
+                    || self
+                        .text_box()
+                        .is_some_and(|text_box| text_box.expand(tolerance).contains(p))
             }
-            AnnotationKind::Text { .. } => self.bounds().expand(tolerance).contains(p),
-            AnnotationKind::Rectangle { rect } => {
+            AnnotationKind::Text { .. } => self.local_bounds().expand(tolerance).contains(p),
+            AnnotationKind::Rectangle {
+                rect,
+                fill,
+                corner_radius,
+            } => {
                 let r = rect.to_rect();
+                let filled = fill.is_some_and(|fill| fill[3] > 0);
+                if *corner_radius > 0.0 {
+                    let outline: Vec<Point> = rounded_rect_outline(r, *corner_radius)
+                        .into_iter()
+                        .map(Point::from_pos2)
+                        .collect();
+                    if filled && point_in_polygon(p, &outline) {
+                        return true;
+                    }
+                    return distance_to_polyline(p, &polygon_outline(&outline, true))
+                        <= tolerance + self.stroke_width.px();
+                }
+                if filled {
+                    return r.expand(tolerance + self.stroke_width.px()).contains(p);
+                }
                 let expanded = r.expand(tolerance + self.stroke_width.px());
                 if !expanded.contains(p) {
                     return false;
@@ -275,7 +871,7 @@ impl Annotation {
                 let inner = r.shrink((self.stroke_width.px() + tolerance).max(1.0));
                 !inner.contains(p)
             }
-            AnnotationKind::Ellipse { rect } => {
+            AnnotationKind::Ellipse { rect, fill } => {
                 let r = rect.to_rect();
                 let center = r.center();
                 let radii = r.size() * 0.5;
@@ -286,8 +882,119 @@ impl Annotation {
                 let ny = (p.y - center.y) / radii.y;
                 let d = nx * nx + ny * ny;
                 let ring = (self.stroke_width.px() + tolerance) / radii.x.min(radii.y).max(1.0);
-                (1.0 - ring).powi(2) <= d && d <= (1.0 + ring).powi(2)
+                let inner = if fill.is_some_and(|fill| fill[3] > 0) {
+                    0.0
+                } else {
+                    (1.0 - ring).powi(2)
+                };
+                inner <= d && d <= (1.0 + ring).powi(2)
+            }
+            AnnotationKind::Stamp { rect, .. } => rect.to_rect().expand(tolerance).contains(p),
+            AnnotationKind::Freehand { points } => {
+                distance_to_polyline(p, points) <= tolerance + self.stroke_width.px()
+            }
+            AnnotationKind::Highlighter { points } => {
+                distance_to_polyline(p, points) <= tolerance + self.line_width() * 0.5
+            }
+            AnnotationKind::Redact { rect, .. } => rect.to_rect().expand(tolerance).contains(p),
+            AnnotationKind::Spotlight { rect, shape, .. } => {
+                let r = rect.to_rect().expand(tolerance);
+                match shape {
+                    SpotlightShape::Rectangle => r.contains(p),
+                    SpotlightShape::Ellipse => point_in_ellipse(p, r),
+                }
+            }
+            AnnotationKind::Step { center, size, .. } => {
+                (p - center.to_pos2()).length() <= step_radius(*size) + tolerance
+            }
+            AnnotationKind::Callout { rect, tail, .. } => {
+                if rect.to_rect().expand(tolerance).contains(p) {
+                    return true;
+                }
+                let (base_a, base_b) = callout_tail_base(*rect, *tail);
+                point_in_triangle(p, base_a.to_pos2(), base_b.to_pos2(), tail.to_pos2())
+                    || distance_to_segment(p, callout_tail_mid(*rect, *tail), tail.to_pos2())
+                        <= tolerance
+            }
+            AnnotationKind::Polygon {
+                points,
+                closed,
+                filled,
+            } => {
+                if *closed && *filled && point_in_polygon(p, points) {
+                    return true;
+                }
+                distance_to_polyline(p, &polygon_outline(points, *closed))
+                    <= tolerance + self.stroke_width.px()
+            }
+            AnnotationKind::Magnifier {
+                source,
+                target,
+                shape,
+                connector,
+            } => {
+                let reach = tolerance + self.stroke_width.px();
+                if source.to_rect().expand(reach).contains(p)
+                    || target.to_rect().expand(reach).contains(p)
+                        && (*shape == LoupeShape::RoundedRect
+                            || point_in_ellipse(p, target.to_rect().expand(reach)))
+                {
+                    return true;
+                }
+                *connector
+                    && magnifier_connector(*source, *target, *shape).is_some_and(|(a, b)| {
+                        distance_to_segment(p, a.to_pos2(), b.to_pos2()) <= reach
+                    })
+            }
+        }
+    }
+
+    pub fn map_points(&mut self, map: impl Fn(Point) -> Point) {
+        let apply = |p: &mut Point| *p = map(*p);
+        let apply_rect = |rect: &mut RectData| {
+            apply(&mut rect.min);
+            apply(&mut rect.max);
+            *rect = rect.normalize();
+        };
+        match &mut self.kind {
+            AnnotationKind::Arrow {
+                from, to, curve, ..
             }
+            | AnnotationKind::ArrowWithText {
+                from, to, curve, ..
+            } => {
+                apply(from);
+                apply(to);
+                match curve {
+                    Some(ArrowCurve::Quadratic { control }) => apply(control),
+                    Some(ArrowCurve::Cubic {
+                        control_a,
+                        control_b,
+                    }) => {
+                        apply(control_a);
+                        apply(control_b);
+                    }
+                    None => {}
+                }
+            }
+            AnnotationKind::Text { pos, .. } => apply(pos),
+            AnnotationKind::Step { center, .. } => apply(center),
+            AnnotationKind::Rectangle { rect, .. }
+            | AnnotationKind::Ellipse { rect, .. }
+            | AnnotationKind::Redact { rect, .. }
+            | AnnotationKind::Spotlight { rect, .. }
+            | AnnotationKind::Stamp { rect, .. } => apply_rect(rect),
+            AnnotationKind::Callout { rect, tail, .. } => {
+                apply_rect(rect);
+                apply(tail);
+            }
+            AnnotationKind::Magnifier { source, target, .. } => {
+                apply_rect(source);
+                apply_rect(target);
+            }
+            AnnotationKind::Freehand { points }
+            | AnnotationKind::Highlighter { points }
+            | AnnotationKind::Polygon { points, .. } => points.iter_mut().for_each(apply),
         }
     }
 
@@ -297,84 +1004,388 @@ impl Annotation {
             p.y += delta.y;
         };
         match &mut self.kind {
-            AnnotationKind::Arrow { from, to } | AnnotationKind::ArrowWithText { from, to, .. } => {
+            AnnotationKind::Arrow {
+                from, to, curve, ..
+            }
+            | AnnotationKind::ArrowWithText {
+                from, to, curve, ..
+            } => {
                 move_point(from);
                 move_point(to);
+                match curve {
+                    Some(ArrowCurve::Quadratic { control }) => move_point(control),
+                    Some(ArrowCurve::Cubic {
+                        control_a,
+                        control_b,
+                    }) => {
+                        move_point(control_a);
+                        move_point(control_b);
+                    }
+                    None => {}
+                }
             }
             AnnotationKind::Text { pos, .. } => move_point(pos),
-            AnnotationKind::Rectangle { rect } | AnnotationKind::Ellipse { rect } => {
+            AnnotationKind::Step { center, .. } => move_point(center),
+            AnnotationKind::Rectangle { rect, .. }
+            | AnnotationKind::Ellipse { rect, .. }
+            | AnnotationKind::Redact { rect, .. }
+            | AnnotationKind::Spotlight { rect, .. }
+            | AnnotationKind::Stamp { rect, .. } => {
                 move_point(&mut rect.min);
                 move_point(&mut rect.max);
             }
+            AnnotationKind::Callout { rect, tail, .. } => {
+                move_point(&mut rect.min);
+                move_point(&mut rect.max);
+                move_point(tail);
+            }
+            AnnotationKind::Magnifier { source, target, .. } => {
+                for rect in [source, target] {
+                    move_point(&mut rect.min);
+                    move_point(&mut rect.max);
+                }
+            }
+            AnnotationKind::Freehand { points }
+            | AnnotationKind::Highlighter { points }
+            | AnnotationKind::Polygon { points, .. } => points.iter_mut().for_each(move_point),
         }
     }
 
-    pub fn handles(&self) -> Vec<(Handle, Point)> {
-        match &self.kind {
-            AnnotationKind::Arrow { from, to } | AnnotationKind::ArrowWithText { from, to, .. } => {
-                vec![(Handle::ArrowFrom, *from), (Handle::ArrowTo, *to)]
-            }
-            AnnotationKind::Text { .. } => vec![],
-            AnnotationKind::Rectangle { rect } | AnnotationKind::Ellipse { rect } => {
-                let r = rect.to_rect();
-                let c = r.center();
-                vec![
-                    (Handle::TopLeft, Point::from_pos2(r.left_top())),
-                    (Handle::Top, Point::new(c.x, r.top())),
-                    (Handle::TopRight, Point::from_pos2(r.right_top())),
-                    (Handle::Right, Point::new(r.right(), c.y)),
-                    (Handle::BottomRight, Point::from_pos2(r.right_bottom())),
-                    (Handle::Bottom, Point::new(c.x, r.bottom())),
-                    (Handle::BottomLeft, Point::from_pos2(r.left_bottom())),
-                    (Handle::Left, Point::new(r.left(), c.y)),
-                ]
-            }
+    pub fn handles(&self, zoom: f32) -> Vec<(Handle, Point)> {
+        let turn = self.turn();
+        let place = |point: Point| Point::from_pos2(turn.apply(point.to_pos2()));
+        let mut handles: Vec<(Handle, Point)> = self
+            .local_handles(zoom)
+            .into_iter()
+            .map(|(handle, point)| (handle, place(point)))
+            .collect();
+        if self.can_rotate() {
+            let top = self.local_bounds().center_top();
+            handles.push((
+                Handle::Rotate,
+                place(Point::new(top.x, top.y - ROTATE_HANDLE_OFFSET)),
+            ));
         }
+        handles
     }
 
-    pub fn resize_from_handle(&mut self, handle: Handle, to: Point, keep_square: bool) {
-        match &mut self.kind {
-            AnnotationKind::Arrow { from, to: target }
+    fn local_handles(&self, zoom: f32) -> Vec<(Handle, Point)> {
+        match &self.kind {
+            AnnotationKind::Arrow {
+                from, to, curve, ..
+            }
             | AnnotationKind::ArrowWithText {
-                from, to: target, ..
-            } => match handle {
-                Handle::ArrowFrom => *from = to,
-                Handle::ArrowTo => *target = to,
-                _ => {}
-            },
-            AnnotationKind::Rectangle { rect } | AnnotationKind::Ellipse { rect } => {
-                let mut r = rect.to_rect();
-                match handle {
-                    Handle::TopLeft => r.min = to.to_pos2(),
-                    Handle::Top => r.min.y = to.y,
-                    Handle::TopRight => {
-                        r.min.y = to.y;
-                        r.max.x = to.x;
-                    }
-                    Handle::Right => r.max.x = to.x,
-                    Handle::BottomRight => r.max = to.to_pos2(),
-                    Handle::Bottom => r.max.y = to.y,
-                    Handle::BottomLeft => {
-                        r.min.x = to.x;
-                        r.max.y = to.y;
+                from, to, curve, ..
+            } => {
+                let mut handles = vec![(Handle::ArrowFrom, *from), (Handle::ArrowTo, *to)];
+                match curve {
+                    Some(ArrowCurve::Cubic {
+                        control_a,
+                        control_b,
+                    }) => {
+                        handles.push((Handle::ArrowControl, *control_a));
+                        handles.push((Handle::ArrowControlB, *control_b));
                     }
-                    Handle::Left => r.min.x = to.x,
-                    _ => {}
+                    // Straight and quadratic arrows are bent from the point
+                    // halfway along the line, which stays on the curve.
+                    _ => handles.push((Handle::ArrowControl, arrow_midpoint(*from, *to, *curve))),
                 }
-
-                if keep_square {
-                    let mut size = r.size();
-                    let side = size.x.abs().max(size.y.abs());
-                    size.x = side * size.x.signum().max(1.0);
-                    size.y = side * size.y.signum().max(1.0);
-                    r.max = r.min + size;
+                handles
+            }
+            AnnotationKind::Text { .. } => self
+                .text_box()
+                .map(|rect| vec![(Handle::TextWidth, Point::from_pos2(rect.right_center()))])
+                .unwrap_or_default(),
+            AnnotationKind::Freehand { .. }
+            | AnnotationKind::Highlighter { .. }
+            | AnnotationKind::Step { .. } => vec![],
+            AnnotationKind::Rectangle {
+                rect,
+                corner_radius,
+                ..
+            } => {
+                let mut handles = rect.handles();
+                let inset = corner_radius_handle_offset(rect.to_rect(), *corner_radius, zoom);
+                let min = rect.normalize().min;
+                handles.push((
+                    Handle::CornerRadius,
+                    Point::new(min.x + inset, min.y + inset),
+                ));
+                handles
+            }
+            AnnotationKind::Ellipse { rect, .. }
+            | AnnotationKind::Redact { rect, .. }
+            | AnnotationKind::Spotlight { rect, .. }
+            | AnnotationKind::Stamp { rect, .. } => rect.handles(),
+            AnnotationKind::Callout { rect, tail, .. } => {
+                let mut handles = rect.handles();
+                handles.push((Handle::CalloutTail, *tail));
+                handles
+            }
+            AnnotationKind::Polygon { points, .. } => points
+                .iter()
+                .enumerate()
+                .map(|(index, point)| (Handle::Vertex(index), *point))
+                .collect(),
+            AnnotationKind::Magnifier { source, target, .. } => {
+                let source = source.normalize();
+                let mut handles = target.handles();
+                handles.push((Handle::SourceTopLeft, source.min));
+                handles.push((Handle::SourceBottomRight, source.max));
+                handles
+            }
+        }
+    }
+
+    pub fn split_arrow_curve(&mut self) {
+        if let AnnotationKind::Arrow {
+            from, to, curve, ..
+        }
+        | AnnotationKind::ArrowWithText {
+            from, to, curve, ..
+        } = &mut self.kind
+        {
+            let quadratic = curve.unwrap_or(ArrowCurve::Quadratic {
+                control: lerp_point(*from, *to, 0.5),
+            });
+            *curve = Some(quadratic.to_cubic(*from, *to));
+        }
+    }
+
+    pub fn resize_from_handle(&mut self, handle: Handle, to: Point, keep_square: bool, zoom: f32) {
+        if handle == Handle::Rotate {
+            let center = self.local_bounds().center();
+            let mut angle = (to.x - center.x).atan2(center.y - to.y);
+            if keep_square {
+                let step = ROTATION_SNAP_DEGREES.to_radians();
+                angle = (angle / step).round() * step;
+            }
+            self.rotation = angle;
+            return;
+        }
+        let turn = self.turn();
+        if turn == Turn::NONE {
+            self.local_resize(handle, to, keep_square, zoom);
+            return;
+        }
+        // Resizing moves the pivot; shift the shape so that the points the
+        // drag leaves in place stay put on the image.
+        let before = turn.center;
+        self.local_resize(
+            handle,
+            Point::from_pos2(turn.invert(to.to_pos2())),
+            keep_square,
+            zoom,
+        );
+        let shift = self.local_bounds().center() - before;
+        self.move_by(turn.apply_vec(shift) - shift);
+    }
+
+    fn local_resize(&mut self, handle: Handle, to: Point, keep_square: bool, zoom: f32) {
+        match &mut self.kind {
+            AnnotationKind::Arrow {
+                from,
+                to: target,
+                curve,
+                ..
+            }
+            | AnnotationKind::ArrowWithText {
+                from,
+                to: target,
+                curve,
+                ..
+            } => match (handle, curve.as_mut()) {
+                (Handle::ArrowFrom, _) => *from = to,
+                (Handle::ArrowTo, _) => *target = to,
+                (
+                    Handle::ArrowControl,
+                    Some(ArrowCurve::Cubic {
+                        control_a: control, ..
+                    }),
+                )
+                | (
+                    Handle::ArrowControlB,
+                    Some(ArrowCurve::Cubic {
+                        control_b: control, ..
+                    }),
+                ) => *control = to,
+                (Handle::ArrowControl, _) => {
+                    // Solve for the control point that puts the curve's
+                    // midpoint under the pointer.
+                    *curve = Some(ArrowCurve::Quadratic {
+                        control: Point::new(
+                            2.0 * to.x - 0.5 * (from.x + target.x),
+                            2.0 * to.y - 0.5 * (from.y + target.y),
+                        ),
+                    });
+                }
+                _ => {}
+            },
+            AnnotationKind::Rectangle {
+                rect,
+                corner_radius,
+                ..
+            } => match handle {
+                Handle::CornerRadius => {
+                    // The handle sits on the diagonal, so average both axes.
+                    let min = rect.normalize().min;
+                    let offset = ((to.x - min.x) + (to.y - min.y)) * 0.5;
+                    *corner_radius = corner_radius_from_handle(rect.to_rect(), offset, zoom);
                 }
+                _ => rect.resize_from_handle(handle, to, keep_square),
+            },
+            AnnotationKind::Ellipse { rect, .. }
+            | AnnotationKind::Redact { rect, .. }
+            | AnnotationKind::Spotlight { rect, .. }
+            | AnnotationKind::Stamp { rect, .. } => {
+                rect.resize_from_handle(handle, to, keep_square)
+            }
+            AnnotationKind::Callout { rect, tail, .. } => match handle {
+                Handle::CalloutTail => *tail = to,
+                _ => rect.resize_from_handle(handle, to, keep_square),
+            },
+            AnnotationKind::Magnifier {
+                source,
+                target,
+                shape,
+                ..
+            } => match handle {
+                Handle::SourceTopLeft => {
+                    *source = RectData {
+                        min: to,
+                        max: source.normalize().max,
+                    }
+                    .normalize()
+                }
+                Handle::SourceBottomRight => {
+                    *source = RectData {
+                        min: source.normalize().min,
+                        max: to,
+                    }
+                    .normalize()
+                }
+                _ => target.resize_from_handle(
+                    handle,
+                    to,
+                    keep_square || *shape == LoupeShape::Circle,
+                ),
+            },
+            AnnotationKind::Polygon { points, .. } => {
+                if let Handle::Vertex(index) = handle {
+                    if let Some(point) = points.get_mut(index) {
+                        *point = to;
+                    }
+                }
+            }
+            AnnotationKind::Text {
+                pos,
+                size,
+                wrap_width,
+                ..
+            } => {
+                if handle == Handle::TextWidth {
+                    *wrap_width = Some((to.x - pos.x).max(size.points()));
+                }
+            }
+            AnnotationKind::Freehand { .. }
+            | AnnotationKind::Highlighter { .. }
+            | AnnotationKind::Step { .. } => {}
+        }
+    }
+
+    pub fn text_font(&self) -> TextFont {
+        match &self.kind {
+            AnnotationKind::Text { font, .. } | AnnotationKind::ArrowWithText { font, .. } => {
+                font.clone()
+            }
+            _ => TextFont::default(),
+        }
+    }
+
+    pub fn text_layout(&self) -> Option<TextLayout> {
+        let plain = |text: &str, size: TextSize, font: &TextFont| {
+            layout_text(
+                font_chain(font),
+                text,
+                size.points(),
+                None,
+                TextAlign::Left,
+                default_line_spacing(),
+            )
+        };
+        match &self.kind {
+            AnnotationKind::Text {
+                content,
+                size,
+                wrap_width,
+                align,
+                line_spacing,
+                font,
+                ..
+            } => Some(layout_text(
+                font_chain(font),
+                content,
+                size.points(),
+                *wrap_width,
+                *align,
+                *line_spacing,
+            )),
+            AnnotationKind::ArrowWithText {
+                text, size, font, ..
+            } => Some(plain(text, *size, font)),
+            AnnotationKind::Callout {
+                rect, text, size, ..
+            } => {
+                let r = rect.normalize();
+                let max_width = (r.max.x - r.min.x - CALLOUT_PADDING * 2.0).max(1.0);
+                Some(layout_text(
+                    font_chain(&TextFont::default()),
+                    text,
+                    size.points(),
+                    Some(max_width),
+                    TextAlign::Left,
+                    default_line_spacing(),
+                ))
+            }
+            AnnotationKind::Step { number, size, .. } => {
+                Some(plain(&number.to_string(), *size, &TextFont::default()))
+            }
+            _ => None,
+        }
+    }
 
-                *rect = RectData::from_rect(r).normalize();
+    pub fn text_origin(&self, layout: &TextLayout) -> Option<Point> {
+        match &self.kind {
+            AnnotationKind::Text { pos, .. } => Some(*pos),
+            AnnotationKind::ArrowWithText { from, to, .. } => Some(arrow_text_anchor(*from, *to)),
+            AnnotationKind::Callout { rect, .. } => {
+                let r = rect.normalize();
+                Some(Point::new(
+                    r.min.x + CALLOUT_PADDING,
+                    r.min.y + CALLOUT_PADDING,
+                ))
             }
-            AnnotationKind::Text { .. } => {}
+            // Step numbers are centred on the badge.
+            AnnotationKind::Step { center, .. } => Some(Point::new(
+                center.x - layout.width * 0.5,
+                center.y - layout.height * 0.5,
+            )),
+            _ => None,
         }
     }
+
+    pub fn text_box(&self) -> Option<Rect> {
+        let size = match &self.kind {
+            AnnotationKind::Text { size, .. } | AnnotationKind::ArrowWithText { size, .. } => *size,
+            _ => return None,
+        };
+        let layout = self.text_layout()?;
+        let origin = self.text_origin(&layout)?;
+        Some(Rect::from_min_size(
+            origin.to_pos2(),
+            Vec2::new(layout.width.max(size.points() * 0.5), layout.height),
+        ))
+    }
 }
 
 impl Point {
@@ -383,6 +1394,332 @@ impl Point {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Turn {
+    pub center: Pos2,
+    pub angle: f32,
+}
+
+impl Turn {
+    pub const NONE: Turn = Turn {
+        center: Pos2::ZERO,
+        angle: 0.0,
+    };
+
+    pub fn apply(self, point: Pos2) -> Pos2 {
+        self.center + self.apply_vec(point - self.center)
+    }
+
+    pub fn invert(self, point: Pos2) -> Pos2 {
+        Turn {
+            angle: -self.angle,
+            ..self
+        }
+        .apply(point)
+    }
+
+    pub fn apply_vec(self, v: Vec2) -> Vec2 {
+        if self.angle == 0.0 {
+            return v;
+        }
+        let (sin, cos) = self.angle.sin_cos();
+        Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+    }
+}
+
+const ROTATE_HANDLE_OFFSET: f32 = 24.0;
+pub const ROTATION_SNAP_DEGREES: f32 = 15.0;
+
+pub const CALLOUT_PADDING: f32 = 10.0;
+pub const CALLOUT_ROUNDING: f32 = 8.0;
+
+pub fn default_callout_tail(rect: RectData) -> Point {
+    let r = rect.to_rect();
+    Point::new(r.left() + r.width() * 0.25, r.bottom() + 28.0)
+}
+
+pub fn callout_tail_base(rect: RectData, tip: Point) -> (Point, Point) {
+    let r = rect.to_rect();
+    let c = r.center();
+    let dx = (tip.x - c.x) / r.width().max(1.0);
+    let dy = (tip.y - c.y) / r.height().max(1.0);
+    let inset = CALLOUT_ROUNDING + 2.0;
+    if dy.abs() >= dx.abs() {
+        let half = (r.width() * 0.15).clamp(4.0, 12.0);
+        let min_x = (r.left() + inset + half).min(c.x);
+        let max_x = (r.right() - inset - half).max(c.x);
+        let x = tip.x.clamp(min_x, max_x);
+        let y = if dy > 0.0 { r.bottom() } else { r.top() };
+        (Point::new(x - half, y), Point::new(x + half, y))
+    } else {
+        let half = (r.height() * 0.15).clamp(4.0, 12.0);
+        let min_y = (r.top() + inset + half).min(c.y);
+        let max_y = (r.bottom() - inset - half).max(c.y);
+        let y = tip.y.clamp(min_y, max_y);
+        let x = if dx > 0.0 { r.right() } else { r.left() };
+        (Point::new(x, y - half), Point::new(x, y + half))
+    }
+}
+
+fn callout_tail_mid(rect: RectData, tip: Point) -> Pos2 {
+    let (a, b) = callout_tail_base(rect, tip);
+    Pos2::new((a.x + b.x) * 0.5, (a.y + b.y) * 0.5)
+}
+
+fn point_in_triangle(p: Pos2, a: Pos2, b: Pos2, c: Pos2) -> bool {
+    let cross = |o: Pos2, u: Pos2, v: Pos2| (u.x - o.x) * (v.y - o.y) - (u.y - o.y) * (v.x - o.x);
+    let d1 = cross(a, b, p);
+    let d2 = cross(b, c, p);
+    let d3 = cross(c, a, p);
+    let has_neg = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let has_pos = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+    !(has_neg && has_pos)
+}
+
+pub fn arrow_text_anchor(from: Point, to: Point) -> Point {
+    let dir = from.delta(to);
+    let len = dir.length().max(1.0);
+    let unit = dir / len;
+    let perp_a = Vec2::new(-unit.y, unit.x);
+    let perp_b = Vec2::new(unit.y, -unit.x);
+    let up_perp = if perp_a.y < perp_b.y { perp_a } else { perp_b };
+    let offset = up_perp * 12.0 + Vec2::new(6.0, -2.0);
+    Point::new(from.x + offset.x, from.y + offset.y)
+}
+
+pub fn step_radius(size: TextSize) -> f32 {
+    size.points() * 0.85 + 4.0
+}
+
+pub const LOUPE_ROUNDING: f32 = 0.18;
+
+const CORNER_RADIUS_HANDLE_INSET: f32 = 10.0;
+
+fn max_corner_radius(rect: Rect) -> f32 {
+    rect.width().min(rect.height()).abs() * 0.5
+}
+
+fn corner_radius_handle_inset(rect: Rect, zoom: f32) -> f32 {
+    (CORNER_RADIUS_HANDLE_INSET / zoom.max(0.01)).min(max_corner_radius(rect) * 0.5)
+}
+
+fn corner_radius_handle_offset(rect: Rect, radius: f32, zoom: f32) -> f32 {
+    let max = max_corner_radius(rect);
+    let inset = corner_radius_handle_inset(rect, zoom);
+    if max <= 0.0 {
+        return inset;
+    }
+    inset + radius.clamp(0.0, max) * (max - inset) / max
+}
+
+fn corner_radius_from_handle(rect: Rect, offset: f32, zoom: f32) -> f32 {
+    let max = max_corner_radius(rect);
+    let inset = corner_radius_handle_inset(rect, zoom);
+    if max <= 0.0 {
+        return 0.0;
+    }
+    ((offset - inset) * max / (max - inset)).clamp(0.0, max)
+}
+
+pub fn rounded_rect_outline(rect: Rect, radius: f32) -> Vec<Pos2> {
+    let r = radius.clamp(0.0, max_corner_radius(rect));
+    let corners = [
+        (rect.right_top(), Vec2::new(-r, 0.0), Vec2::new(0.0, r)),
+        (rect.right_bottom(), Vec2::new(0.0, -r), Vec2::new(-r, 0.0)),
+        (rect.left_bottom(), Vec2::new(r, 0.0), Vec2::new(0.0, -r)),
+        (rect.left_top(), Vec2::new(0.0, r), Vec2::new(r, 0.0)),
+    ];
+    let mut points = Vec::with_capacity(corners.len() * 9);
+    for (corner, into, out) in corners {
+        let (start, end) = (corner + into, corner + out);
+        for step in 0..=8 {
+            let t = step as f32 / 8.0;
+            let u = 1.0 - t;
+            points.push(Pos2::new(
+                u * u * start.x + 2.0 * u * t * corner.x + t * t * end.x,
+                u * u * start.y + 2.0 * u * t * corner.y + t * t * end.y,
+            ));
+        }
+    }
+    points
+}
+
+pub fn default_loupe_target(source: RectData, image_size: Vec2) -> RectData {
+    let source = source.to_rect();
+    let side = (source.width().max(source.height()) * 2.5).max(96.0);
+    let gap = 24.0;
+    let mut min = Pos2::new(source.right() + gap, source.center().y - side * 0.5);
+    if min.x + side > image_size.x && source.left() - gap - side >= 0.0 {
+        min.x = source.left() - gap - side;
+    }
+    min.x = min.x.min(image_size.x - side).max(0.0);
+    min.y = min.y.min(image_size.y - side).max(0.0);
+    RectData::from_rect(Rect::from_min_size(min, Vec2::splat(side)))
+}
+
+pub fn magnifier_connector(
+    source: RectData,
+    target: RectData,
+    shape: LoupeShape,
+) -> Option<(Point, Point)> {
+    let source = source.to_rect();
+    let target = target.to_rect();
+    if source.intersects(target) {
+        return None;
+    }
+    let direction = target.center() - source.center();
+    let start = source.center() + direction * rect_exit(source, direction, shape);
+    let end = target.center() - direction * rect_exit(target, -direction, shape);
+    Some((Point::from_pos2(start), Point::from_pos2(end)))
+}
+
+fn rect_exit(rect: Rect, direction: Vec2, shape: LoupeShape) -> f32 {
+    let half = rect.size() * 0.5;
+    let nx = direction.x / half.x.max(0.5);
+    let ny = direction.y / half.y.max(0.5);
+    match shape {
+        LoupeShape::Circle => 1.0 / (nx * nx + ny * ny).sqrt().max(f32::EPSILON),
+        LoupeShape::RoundedRect => 1.0 / nx.abs().max(ny.abs()).max(f32::EPSILON),
+    }
+}
+
+fn point_in_ellipse(p: Pos2, rect: Rect) -> bool {
+    let radii = rect.size() * 0.5;
+    if radii.x <= 0.1 || radii.y <= 0.1 {
+        return false;
+    }
+    let offset = p - rect.center();
+    (offset.x / radii.x).powi(2) + (offset.y / radii.y).powi(2) <= 1.0
+}
+
+pub const POLYGON_FILL_ALPHA: f32 = 0.3;
+
+pub fn polygon_outline(points: &[Point], closed: bool) -> Vec<Point> {
+    let mut outline = points.to_vec();
+    if closed && points.len() > 2 {
+        outline.push(points[0]);
+    }
+    outline
+}
+
+fn point_in_polygon(p: Pos2, points: &[Point]) -> bool {
+    let mut inside = false;
+    let mut j = points.len().wrapping_sub(1);
+    for (i, a) in points.iter().enumerate() {
+        let b = points[j];
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+// Splits the outline into horizontal slabs at every vertex and crossing, then
+// pairs the edges inside each slab left to right, matching the even-odd rule
+// the export fills with.
+pub fn triangulate_polygon(points: &[Point]) -> Vec<[Point; 3]> {
+    if points.len() < 3 {
+        return Vec::new();
+    }
+    let edges: Vec<(Point, Point)> = (0..points.len())
+        .map(|i| (points[i], points[(i + 1) % points.len()]))
+        .filter(|(a, b)| a.y != b.y)
+        .collect();
+    let mut rows: Vec<f32> = points.iter().map(|point| point.y).collect();
+    for (i, &(a, b)) in edges.iter().enumerate() {
+        for &(c, d) in &edges[i + 1..] {
+            let r = (b.x - a.x, b.y - a.y);
+            let q = (d.x - c.x, d.y - c.y);
+            let denom = r.0 * q.1 - r.1 * q.0;
+            if denom.abs() <= f32::EPSILON {
+                continue;
+            }
+            let t = ((c.x - a.x) * q.1 - (c.y - a.y) * q.0) / denom;
+            let u = ((c.x - a.x) * r.1 - (c.y - a.y) * r.0) / denom;
+            if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+                rows.push(a.y + t * r.1);
+            }
+        }
+    }
+    rows.sort_by(f32::total_cmp);
+    rows.dedup();
+
+    let x_at = |(a, b): (Point, Point), y: f32| a.x + (b.x - a.x) * (y - a.y) / (b.y - a.y);
+    let mut triangles = Vec::new();
+    for slab in rows.windows(2) {
+        let (top, bottom) = (slab[0], slab[1]);
+        let mid = (top + bottom) * 0.5;
+        let mut crossing: Vec<(Point, Point)> = edges
+            .iter()
+            .copied()
+            .filter(|(a, b)| a.y.min(b.y) < mid && mid < a.y.max(b.y))
+            .collect();
+        crossing.sort_by(|l, r| x_at(*l, mid).total_cmp(&x_at(*r, mid)));
+        for pair in crossing.chunks_exact(2) {
+            let (left, right) = (pair[0], pair[1]);
+            let top_left = Point::new(x_at(left, top), top);
+            let top_right = Point::new(x_at(right, top), top);
+            let bottom_left = Point::new(x_at(left, bottom), bottom);
+            let bottom_right = Point::new(x_at(right, bottom), bottom);
+            triangles.push([top_left, top_right, bottom_right]);
+            triangles.push([top_left, bottom_right, bottom_left]);
+        }
+    }
+    triangles
+}
+
+const CURVE_SEGMENTS: usize = 32;
+
+pub fn arrow_polyline(from: Point, to: Point, curve: Option<ArrowCurve>) -> Vec<Point> {
+    match curve {
+        None => vec![from, to],
+        Some(curve) => (0..=CURVE_SEGMENTS)
+            .map(|step| curve.point_at(from, to, step as f32 / CURVE_SEGMENTS as f32))
+            .collect(),
+    }
+}
+
+pub fn arrow_end_directions(from: Point, to: Point, curve: Option<ArrowCurve>) -> (Vec2, Vec2) {
+    let (start_ref, end_ref) = match curve {
+        None => (to, from),
+        Some(ArrowCurve::Quadratic { control }) => (control, control),
+        Some(ArrowCurve::Cubic {
+            control_a,
+            control_b,
+        }) => (control_a, control_b),
+    };
+    let mut start = from.delta(start_ref);
+    let mut end = to.delta(end_ref);
+    // A control point sitting on an endpoint has no tangent; fall back to the chord.
+    if start.length_sq() <= f32::EPSILON {
+        start = from.delta(to);
+    }
+    if end.length_sq() <= f32::EPSILON {
+        end = to.delta(from);
+    }
+    (start, end)
+}
+
+fn arrow_midpoint(from: Point, to: Point, curve: Option<ArrowCurve>) -> Point {
+    match curve {
+        None => lerp_point(from, to, 0.5),
+        Some(curve) => curve.point_at(from, to, 0.5),
+    }
+}
+
+fn lerp_point(a: Point, b: Point, t: f32) -> Point {
+    Point::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t)
+}
+
+fn polyline_rect(points: &[Point]) -> Rect {
+    let mut rect = Rect::NOTHING;
+    for point in points {
+        rect.extend_with(point.to_pos2());
+    }
+    rect
+}
+
 fn distance_to_segment(point: Pos2, a: Pos2, b: Pos2) -> f32 {
     let ab = b - a;
     let ap = point - a;
@@ -395,9 +1732,88 @@ fn distance_to_segment(point: Pos2, a: Pos2, b: Pos2) -> f32 {
     (point - projection).length()
 }
 
+fn distance_to_polyline(point: Pos2, points: &[Point]) -> f32 {
+    match points {
+        [] => f32::INFINITY,
+        [single] => (point - single.to_pos2()).length(),
+        _ => points
+            .windows(2)
+            .map(|pair| distance_to_segment(point, pair[0].to_pos2(), pair[1].to_pos2()))
+            .fold(f32::INFINITY, f32::min),
+    }
+}
+
+pub fn smooth_freehand(points: &[Point]) -> Vec<Point> {
+    const SMOOTHING_PASSES: usize = 2;
+    const SIMPLIFY_TOLERANCE: f32 = 0.75;
+
+    let mut path: Vec<Point> = Vec::with_capacity(points.len());
+    for point in points {
+        if path
+            .last()
+            .is_none_or(|last: &Point| last.delta(*point).length_sq() > 0.01)
+        {
+            path.push(*point);
+        }
+    }
+    if path.len() < 3 {
+        return path;
+    }
+
+    for _ in 0..SMOOTHING_PASSES {
+        let mut smoothed = Vec::with_capacity(path.len() * 2);
+        smoothed.push(path[0]);
+        for pair in path.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            smoothed.push(Point::new(a.x * 0.75 + b.x * 0.25, a.y * 0.75 + b.y * 0.25));
+            smoothed.push(Point::new(a.x * 0.25 + b.x * 0.75, a.y * 0.25 + b.y * 0.75));
+        }
+        smoothed.push(path[path.len() - 1]);
+        path = smoothed;
+    }
+
+    simplify_polyline(&path, SIMPLIFY_TOLERANCE)
+}
+
+fn simplify_polyline(points: &[Point], tolerance: f32) -> Vec<Point> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut stack = vec![(0usize, points.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let a = points[first].to_pos2();
+        let b = points[last].to_pos2();
+        let mut farthest = (0usize, 0.0f32);
+        for (idx, point) in points.iter().enumerate().take(last).skip(first + 1) {
+            let distance = distance_to_segment(point.to_pos2(), a, b);
+            if distance > farthest.1 {
+                farthest = (idx, distance);
+            }
+        }
+        if farthest.1 > tolerance {
+            keep[farthest.0] = true;
+            stack.push((first, farthest.0));
+            stack.push((farthest.0, last));
+        }
+    }
+
+    points
+        .iter()
+        .zip(keep)
+        .filter_map(|(point, keep)| keep.then_some(*point))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Annotation, AnnotationKind, Point, RectData, StrokeWidth, TextSize};
+    use super::{
+        point_in_triangle, smooth_freehand, triangulate_polygon, Annotation, AnnotationKind,
+        ArrowHead, Handle, Point, Pos2, RectData, StrokeStyle, StrokeWidth, TextEffects, TextSize,
+    };
 
     #[test]
     fn move_rectangle_changes_bounds() {
@@ -408,9 +1824,14 @@ mod tests {
                     min: Point::new(10.0, 10.0),
                     max: Point::new(20.0, 20.0),
                 },
+                fill: None,
+                corner_radius: 0.0,
             },
             color: [0, 0, 0, 255],
-            stroke_width: StrokeWidth::Medium,
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
         };
 
         annotation.move_by(egui::Vec2::new(5.0, -3.0));
@@ -419,6 +1840,125 @@ mod tests {
         assert_eq!(bounds.min.y, 3.0);
     }
 
+    #[test]
+    fn filled_shapes_hit_test_their_whole_interior() {
+        let rect = RectData {
+            min: Point::new(0.0, 0.0),
+            max: Point::new(100.0, 60.0),
+        };
+        let shape = |kind| Annotation {
+            id: 1,
+            kind,
+            color: [0, 0, 0, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        };
+        let center = Point::new(50.0, 30.0);
+
+        let hollow = shape(AnnotationKind::Ellipse { rect, fill: None });
+        assert!(!hollow.contains(center, 2.0));
+        let clear = shape(AnnotationKind::Rectangle {
+            rect,
+            fill: Some([255, 0, 0, 0]),
+            corner_radius: 0.0,
+        });
+        assert!(!clear.contains(center, 2.0));
+
+        let ellipse = shape(AnnotationKind::Ellipse {
+            rect,
+            fill: Some([255, 0, 0, 64]),
+        });
+        assert!(ellipse.contains(center, 2.0));
+        assert!(!ellipse.contains(Point::new(3.0, 3.0), 2.0));
+        let rectangle = shape(AnnotationKind::Rectangle {
+            rect,
+            fill: Some([255, 0, 0, 64]),
+            corner_radius: 0.0,
+        });
+        assert!(rectangle.contains(Point::new(3.0, 3.0), 2.0));
+    }
+
+    #[test]
+    fn rounded_rectangle_hit_tests_its_rounded_outline() {
+        let mut annotation = Annotation {
+            id: 1,
+            kind: AnnotationKind::Rectangle {
+                rect: RectData {
+                    min: Point::new(0.0, 0.0),
+                    max: Point::new(100.0, 60.0),
+                },
+                fill: None,
+                corner_radius: 0.0,
+            },
+            color: [0, 0, 0, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        };
+        assert!(annotation.contains(Point::new(1.0, 1.0), 2.0));
+
+        // The 10 px inset to the centre at 30 px covers radii 0 to 30.
+        annotation.resize_from_handle(Handle::CornerRadius, Point::new(26.0, 26.0), false, 1.0);
+        let AnnotationKind::Rectangle { corner_radius, .. } = annotation.kind else {
+            unreachable!();
+        };
+        assert_eq!(corner_radius, 24.0);
+        assert!(!annotation.contains(Point::new(1.0, 1.0), 2.0));
+        assert!(annotation.contains(Point::new(50.0, 0.0), 2.0));
+        assert!(annotation.contains(Point::new(0.0, 30.0), 2.0));
+
+        annotation.resize_from_handle(Handle::CornerRadius, Point::new(500.0, 500.0), false, 1.0);
+        let AnnotationKind::Rectangle { corner_radius, .. } = annotation.kind else {
+            unreachable!();
+        };
+        assert_eq!(corner_radius, 30.0);
+    }
+
+    #[test]
+    fn corner_radius_handle_stays_clear_of_the_corners_on_small_rectangles() {
+        let mut annotation = Annotation {
+            id: 1,
+            kind: AnnotationKind::Rectangle {
+                rect: RectData {
+                    min: Point::new(0.0, 0.0),
+                    max: Point::new(16.0, 16.0),
+                },
+                fill: None,
+                corner_radius: 8.0,
+            },
+            color: [0, 0, 0, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        };
+        for zoom in [0.25, 1.0, 4.0] {
+            let handles = annotation.handles(zoom);
+            let radius_handle = |handles: &[(Handle, Point)]| {
+                handles
+                    .iter()
+                    .find(|(handle, _)| *handle == Handle::CornerRadius)
+                    .map(|(_, point)| *point)
+                    .expect("radius handle")
+            };
+            let point = radius_handle(&handles);
+            assert!(point.x > 0.0 && point.x <= 8.0, "{point:?} at {zoom}x");
+            assert!(point.y > 0.0 && point.y <= 8.0, "{point:?} at {zoom}x");
+
+            annotation.resize_from_handle(Handle::CornerRadius, point, false, zoom);
+            let AnnotationKind::Rectangle { corner_radius, .. } = annotation.kind else {
+                unreachable!();
+            };
+            assert!(
+                (corner_radius - 8.0).abs() < 1e-3,
+                "{corner_radius} at {zoom}x"
+            );
+        }
+    }
+
     #[test]
     fn hit_test_arrow_line() {
         let annotation = Annotation {
@@ -426,15 +1966,196 @@ mod tests {
             kind: AnnotationKind::Arrow {
                 from: Point::new(0.0, 0.0),
                 to: Point::new(100.0, 0.0),
+                head: ArrowHead::Filled,
+                curve: None,
             },
             color: [0, 0, 0, 255],
-            stroke_width: StrokeWidth::Medium,
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
         };
 
         assert!(annotation.contains(Point::new(50.0, 1.0), 2.0));
         assert!(!annotation.contains(Point::new(50.0, 20.0), 2.0));
     }
 
+    #[test]
+    fn l_shaped_polygon_fills_and_hit_tests_only_its_interior() {
+        let points = vec![
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 0.0),
+            Point::new(100.0, 40.0),
+            Point::new(40.0, 40.0),
+            Point::new(40.0, 100.0),
+            Point::new(0.0, 100.0),
+        ];
+        let area: f32 = triangulate_polygon(&points)
+            .iter()
+            .map(|[a, b, c]| ((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)).abs() * 0.5)
+            .sum();
+        assert!((area - 6400.0).abs() < 0.01);
+
+        let mut annotation = Annotation {
+            id: 1,
+            kind: AnnotationKind::Polygon {
+                points,
+                closed: true,
+                filled: true,
+            },
+            color: [0, 0, 0, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        };
+        assert!(annotation.contains(Point::new(20.0, 80.0), 2.0));
+        assert!(!annotation.contains(Point::new(80.0, 80.0), 2.0));
+
+        annotation.resize_from_handle(Handle::Vertex(3), Point::new(90.0, 90.0), false, 1.0);
+        assert!(annotation.contains(Point::new(80.0, 80.0), 2.0));
+    }
+
+    #[test]
+    fn crossed_quad_fills_with_the_even_odd_rule() {
+        let points = vec![
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 100.0),
+            Point::new(100.0, 0.0),
+            Point::new(0.0, 100.0),
+        ];
+        let triangles = triangulate_polygon(&points);
+        let covered = |x: f32, y: f32| {
+            triangles.iter().any(|[a, b, c]| {
+                point_in_triangle(Pos2::new(x, y), a.to_pos2(), b.to_pos2(), c.to_pos2())
+            })
+        };
+        let area: f32 = triangles
+            .iter()
+            .map(|[a, b, c]| ((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)).abs() * 0.5)
+            .sum();
+
+        assert!((area - 5000.0).abs() < 0.01);
+        assert!(covered(20.0, 50.0));
+        assert!(covered(80.0, 50.0));
+        assert!(!covered(50.0, 20.0));
+        assert!(!covered(50.0, 80.0));
+    }
+
+    #[test]
+    fn dragging_bend_handle_curves_arrow_through_pointer() {
+        let mut annotation = Annotation {
+            id: 1,
+            kind: AnnotationKind::Arrow {
+                from: Point::new(0.0, 0.0),
+                to: Point::new(100.0, 0.0),
+                head: ArrowHead::Filled,
+                curve: None,
+            },
+            color: [0, 0, 0, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        };
+
+        annotation.resize_from_handle(Handle::ArrowControl, Point::new(50.0, 40.0), false, 1.0);
+
+        let bend = annotation
+            .handles(1.0)
+            .into_iter()
+            .find(|(handle, _)| *handle == Handle::ArrowControl)
+            .map(|(_, point)| point);
+        assert_eq!(bend, Some(Point::new(50.0, 40.0)));
+        assert!(annotation.contains(Point::new(50.0, 40.0), 2.0));
+        assert!(!annotation.contains(Point::new(50.0, 0.0), 2.0));
+        assert!(annotation.bounds().max.y >= 40.0);
+
+        annotation.split_arrow_curve();
+        assert!(annotation.contains(Point::new(50.0, 40.0), 2.0));
+        assert_eq!(annotation.handles(1.0).len(), 4);
+    }
+
+    #[test]
+    fn freehand_is_smoothed_and_hit_tested_along_path() {
+        let raw: Vec<Point> = (0..=100)
+            .map(|x| Point::new(x as f32, if x % 2 == 0 { 0.0 } else { 0.4 }))
+            .collect();
+        let points = smooth_freehand(&raw);
+        assert!(points.len() < raw.len() / 4);
+        assert_eq!(points.first().map(|p| p.x), Some(0.0));
+        assert_eq!(points.last().map(|p| p.x), Some(100.0));
+
+        let annotation = Annotation {
+            id: 1,
+            kind: AnnotationKind::Freehand { points },
+            color: [0, 0, 0, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        };
+        assert!(annotation.contains(Point::new(50.0, 2.0), 2.0));
+        assert!(!annotation.contains(Point::new(50.0, 20.0), 2.0));
+    }
+
+    #[test]
+    fn callout_tail_handle_moves_tip_and_is_hit_tested() {
+        let mut annotation = Annotation {
+            id: 1,
+            kind: AnnotationKind::Callout {
+                rect: RectData {
+                    min: Point::new(0.0, 0.0),
+                    max: Point::new(100.0, 40.0),
+                },
+                tail: Point::new(25.0, 70.0),
+                text: "Click here".to_string(),
+                size: TextSize::M,
+            },
+            color: [0, 0, 0, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        };
+        assert!(annotation.contains(Point::new(25.0, 60.0), 1.0));
+        assert!(annotation
+            .handles(1.0)
+            .iter()
+            .any(|(handle, point)| *handle == Handle::CalloutTail && point.y == 70.0));
+
+        annotation.resize_from_handle(Handle::CalloutTail, Point::new(160.0, 20.0), false, 1.0);
+        assert!(annotation.contains(Point::new(150.0, 20.0), 1.0));
+        assert!(!annotation.contains(Point::new(25.0, 60.0), 1.0));
+        assert!(annotation.bounds().contains(egui::Pos2::new(160.0, 20.0)));
+    }
+
+    #[test]
+    fn arrow_without_head_deserializes_as_filled() {
+        let legacy: AnnotationKind =
+            serde_json::from_str(r#"{"Arrow":{"from":{"x":0.0,"y":0.0},"to":{"x":5.0,"y":5.0}}}"#)
+                .expect("legacy arrow");
+        assert!(matches!(
+            legacy,
+            AnnotationKind::Arrow {
+                head: ArrowHead::Filled,
+                ..
+            }
+        ));
+
+        let plain: AnnotationKind = serde_json::from_str(
+            r#"{"Arrow":{"from":{"x":0.0,"y":0.0},"to":{"x":5.0,"y":5.0},"head":"None"}}"#,
+        )
+        .expect("plain line");
+        assert!(matches!(
+            plain,
+            AnnotationKind::Arrow {
+                head: ArrowHead::None,
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn text_size_deserializes_legacy_and_numeric() {
         let legacy: TextSize = serde_json::from_str("\"M\"").expect("legacy text size");
@@ -446,4 +2167,134 @@ mod tests {
         let clamped: TextSize = serde_json::from_str("100").expect("clamped text size");
         assert_eq!(clamped.as_u8(), TextSize::MAX);
     }
+
+    #[test]
+    fn legacy_text_is_plain_and_plate_grows_its_bounds() {
+        let legacy: AnnotationKind = serde_json::from_str(
+            r#"{"Text":{"pos":{"x":10.0,"y":10.0},"content":"Hi","size":18}}"#,
+        )
+        .expect("legacy text");
+        let AnnotationKind::Text { effects, .. } = &legacy else {
+            panic!("expected text");
+        };
+        assert_eq!(*effects, TextEffects::default());
+        assert!(effects.outline_offsets(1.0).is_empty());
+
+        let mut annotation = Annotation {
+            id: 1,
+            kind: legacy,
+            color: [0, 0, 0, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        };
+        let plain = annotation.bounds();
+        if let AnnotationKind::Text { effects, .. } = &mut annotation.kind {
+            effects.background = true;
+        }
+        let plated = annotation.bounds();
+        let padding = TextEffects::plate_padding(TextSize::M);
+        assert_eq!(plated.min.x, plain.min.x - padding);
+        assert_eq!(plated.max.y, plain.max.y + padding);
+    }
+
+    #[test]
+    fn narrowing_the_text_box_wraps_into_taller_bounds() {
+        let kind: AnnotationKind = serde_json::from_str(
+            r#"{"Text":{"pos":{"x":0.0,"y":0.0},"content":"one two three four","size":18}}"#,
+        )
+        .expect("legacy text");
+        let mut annotation = Annotation {
+            id: 1,
+            kind,
+            color: [0, 0, 0, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        };
+        let single_line = annotation.bounds();
+        let (handle, _) = annotation.handles(1.0)[0];
+        assert_eq!(handle, Handle::TextWidth);
+
+        annotation.resize_from_handle(handle, Point { x: 60.0, y: 0.0 }, false, 1.0);
+        let wrapped = annotation.bounds();
+        assert_eq!(wrapped.width(), 60.0);
+        assert!(wrapped.height() > single_line.height() * 2.0);
+    }
+
+    #[test]
+    fn rotated_rectangles_hit_test_resize_and_snap_in_their_own_frame() {
+        let mut annotation = Annotation {
+            id: 1,
+            kind: AnnotationKind::Rectangle {
+                rect: RectData {
+                    min: Point::new(0.0, 40.0),
+                    max: Point::new(100.0, 60.0),
+                },
+                fill: Some([0, 0, 0, 255]),
+                corner_radius: 0.0,
+            },
+            color: [0, 0, 0, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        };
+        let (rotate, _) = *annotation.handles(1.0).last().expect("rotation handle");
+        assert_eq!(rotate, Handle::Rotate);
+
+        // Dragging the handle to the right of the pivot turns the bar upright;
+        // Shift snaps a near miss to the 15° grid.
+        annotation.resize_from_handle(Handle::Rotate, Point::new(100.0, 53.0), true, 1.0);
+        assert!((annotation.rotation - std::f32::consts::FRAC_PI_2).abs() < 1e-4);
+        assert!(annotation.contains(Point::new(50.0, 5.0), 1.0));
+        assert!(!annotation.contains(Point::new(5.0, 50.0), 1.0));
+        let bounds = annotation.bounds();
+        assert!(bounds.height() > 100.0 && bounds.width() < 40.0);
+
+        // Resizing from a turned corner keeps the opposite corner in place.
+        let corner = |annotation: &Annotation, handle| {
+            annotation
+                .handles(1.0)
+                .into_iter()
+                .find(|(candidate, _)| *candidate == handle)
+                .map(|(_, point)| point)
+                .expect("corner handle")
+        };
+        let anchor = corner(&annotation, Handle::TopLeft);
+        let dragged = corner(&annotation, Handle::BottomRight);
+        annotation.resize_from_handle(
+            Handle::BottomRight,
+            Point::new(dragged.x - 10.0, dragged.y + 30.0),
+            false,
+            1.0,
+        );
+        let moved = corner(&annotation, Handle::TopLeft);
+        assert!((moved.x - anchor.x).abs() < 1e-3 && (moved.y - anchor.y).abs() < 1e-3);
+        let RectData { min, max } = match annotation.kind {
+            AnnotationKind::Rectangle { rect, .. } => rect.normalize(),
+            _ => unreachable!(),
+        };
+        assert!(((max.x - min.x) - 130.0).abs() < 1e-3);
+        assert!(((max.y - min.y) - 30.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn stroke_width_deserializes_legacy_and_numeric() {
+        let legacy: StrokeWidth = serde_json::from_str("\"Thick\"").expect("legacy width");
+        assert_eq!(legacy.px(), 5.0);
+
+        let numeric: StrokeWidth = serde_json::from_str("12.5").expect("numeric width");
+        assert_eq!(numeric.px(), 12.5);
+
+        let clamped: StrokeWidth = serde_json::from_str("0").expect("clamped width");
+        assert_eq!(clamped.px(), StrokeWidth::MIN);
+
+        let round_trip: StrokeWidth =
+            serde_json::from_str(&serde_json::to_string(&numeric).expect("serialize"))
+                .expect("round trip");
+        assert_eq!(round_trip, numeric);
+    }
 }
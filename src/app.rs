@@ -11,8 +11,11 @@ use crate::action_bar;
 use crate::canvas;
 use crate::clipboard::{self, ClipboardPayload, ClipboardWatcher, WatcherEvent};
 use crate::flatten;
+use crate::fonts;
 use crate::platform;
-use crate::state::{AppUiFlags, EditorState, PendingImage, PendingImageSource, VisualEffectsMode};
+use crate::state::{
+    AppUiFlags, EditorState, PendingImage, PendingImageSource, VisualEffectsMode, ZOrder,
+};
 use crate::theme;
 use crate::toolbar;
 use crate::ui_controls;
@@ -36,6 +39,7 @@ impl SnapMarkApp {
         let show_dock_signal = Arc::new(AtomicBool::new(false));
         let theme = theme::premium_dark_theme();
         theme::apply_theme(&cc.egui_ctx, &theme);
+        fonts::install(&cc.egui_ctx);
 
         let mut state = EditorState::default();
         state.theme_density = theme.controls.global_spacing_scale;
@@ -199,6 +203,8 @@ impl SnapMarkApp {
         if ctx.input(|input| input.key_pressed(Key::Escape)) {
             if self.state.text_edit.is_some() {
                 self.state.text_edit = None;
+            } else if !self.state.polygon_draft.is_empty() {
+                self.state.polygon_draft.clear();
             } else if self.state.active_tool != crate::annotation::Tool::Select {
                 self.state.set_tool(crate::annotation::Tool::Select);
             } else if self.state.image.is_some() {
@@ -226,6 +232,38 @@ impl SnapMarkApp {
             if ctx.input(|input| input.key_pressed(Key::E)) {
                 self.state.set_tool(crate::annotation::Tool::Ellipse);
             }
+            if ctx.input(|input| input.key_pressed(Key::P)) {
+                self.state.set_tool(crate::annotation::Tool::Freehand);
+            }
+            if ctx.input(|input| input.key_pressed(Key::H)) {
+                self.state.set_tool(crate::annotation::Tool::Highlighter);
+            }
+            if ctx.input(|input| input.key_pressed(Key::B)) {
+                self.state.set_tool(crate::annotation::Tool::Redact);
+            }
+            if ctx.input(|input| input.key_pressed(Key::N)) {
+                self.state.set_tool(crate::annotation::Tool::Step);
+            }
+            if ctx.input(|input| input.key_pressed(Key::C)) {
+                self.state.set_tool(crate::annotation::Tool::Callout);
+            }
+            if ctx.input(|input| input.key_pressed(Key::S)) {
+                self.state.set_tool(crate::annotation::Tool::Spotlight);
+            }
+            if ctx.input(|input| input.key_pressed(Key::M)) {
+                self.state.set_tool(crate::annotation::Tool::Magnifier);
+            }
+            if ctx.input(|input| input.key_pressed(Key::L)) {
+                self.state.set_tool(crate::annotation::Tool::Polygon);
+            }
+            if ctx.input(|input| input.key_pressed(Key::K)) {
+                self.state.set_tool(crate::annotation::Tool::Stamp);
+            }
+            if !self.state.polygon_draft.is_empty()
+                && ctx.input(|input| input.key_pressed(Key::Enter))
+            {
+                self.state.finish_polygon(!shift);
+            }
 
             if ctx
                 .input(|input| input.key_pressed(Key::Delete) || input.key_pressed(Key::Backspace))
@@ -252,6 +290,42 @@ impl SnapMarkApp {
             self.paste_image(ctx);
         }
 
+        if self.state.text_edit.is_none() && ctx.input(|input| input.key_pressed(Key::A)) {
+            self.state.set_tool(crate::annotation::Tool::Select);
+            self.state.select_all();
+        }
+
+        if self.state.text_edit.is_none() && ctx.input(|input| input.key_pressed(Key::G)) {
+            if shift {
+                self.state.ungroup_selected();
+            } else {
+                self.state.group_selected();
+            }
+        }
+
+        if self.state.text_edit.is_none() {
+            let order = ctx.input(|input| {
+                if input.key_pressed(Key::CloseBracket) {
+                    Some(if shift {
+                        ZOrder::BringToFront
+                    } else {
+                        ZOrder::BringForward
+                    })
+                } else if input.key_pressed(Key::OpenBracket) {
+                    Some(if shift {
+                        ZOrder::SendToBack
+                    } else {
+                        ZOrder::SendBackward
+                    })
+                } else {
+                    None
+                }
+            });
+            if let Some(order) = order {
+                self.state.reorder_selected(order);
+            }
+        }
+
         if ctx.input(|input| input.key_pressed(Key::Z)) {
             if shift {
                 self.state.redo();
@@ -323,7 +397,7 @@ impl SnapMarkApp {
         self.state.image = None;
         self.state.annotations.clear();
         self.state.history.clear_with(Vec::new());
-        self.state.selection = None;
+        self.state.selection.clear();
         self.state.drag_state = None;
         self.state.text_edit = None;
         self.state.window_open = false;
@@ -1,10 +1,29 @@
+use std::borrow::Cow;
+
+use egui::epaint::{TextShape, Vertex};
 use egui::{
-    vec2, Align, Align2, Color32, Context, FontId, Id, Key, Layout, Painter, Pos2, Rect, Response,
-    ScrollArea, Sense, Shape, Stroke, Ui,
+    vec2, Align, Align2, Button, Color32, ColorImage, Context, FontId, Id, Key, Layout, Mesh,
+    Painter, Pos2, Rect, Response, ScrollArea, Sense, Shape, Stroke, TextureId, TextureOptions, Ui,
+    Vec2,
 };
 
-use crate::annotation::{Annotation, AnnotationKind, Handle, Point, RectData, TextSize, Tool};
-use crate::state::{DragMode, DragState, EditorState, TextEditState, TextEditTarget};
+use crate::annotation::{
+    arrow_end_directions, arrow_polyline, arrow_text_anchor, callout_tail_base,
+    default_callout_tail, default_loupe_target, magnifier_connector, rounded_rect_outline,
+    smooth_freehand, step_radius, triangulate_polygon, Annotation, AnnotationKind, ArrowCurve,
+    ArrowHead, Handle, LoupeShape, Point, RectData, RedactMode, SpotlightShape, StampIcon,
+    StrokeStyle, TextEffects, TextSize, Tool, Turn, CALLOUT_ROUNDING, DEFAULT_STAMP_SIZE,
+    LOUPE_ROUNDING, TEXT_SHADOW_COLOR,
+};
+use crate::fonts::{egui_family, font_chain};
+use crate::redaction::{self, PixelRegion};
+use crate::spotlight::{self, Spotlight};
+use crate::stamp::{stamp_parts, Ink, StampPart};
+use crate::state::{
+    AlignEdge, Distribute, DragMode, DragState, EditorImage, EditorState, RedactionPreview,
+    SpotlightGray, TextEditState, TextEditTarget, TextHalo, ZOrder,
+};
+use crate::text_layout::{line_glyphs, TextStamp};
 use crate::theme;
 
 pub fn show_canvas(ui: &mut Ui, ctx: &Context, state: &mut EditorState) {
@@ -22,6 +41,10 @@ pub fn show_canvas(ui: &mut Ui, ctx: &Context, state: &mut EditorState) {
         )
     };
 
+    sync_redaction_previews(ctx, state);
+    sync_spotlight_gray(ctx, state);
+    sync_text_halos(ctx, state);
+
     let available = ui.available_size();
     if state.fit_zoom_to_view {
         state.set_fit_zoom(image_size, available - vec2(48.0, 48.0));
@@ -69,9 +92,11 @@ pub fn show_canvas(ui: &mut Ui, ctx: &Context, state: &mut EditorState) {
 
             draw_annotations(&painter, state, image_rect);
             draw_drag_preview(&painter, state, image_rect);
+            draw_polygon_draft(&painter, state, image_rect);
             draw_selection(&painter, state, image_rect);
 
             let _ = handle_pointer_interaction(ctx, state, &response, image_rect);
+            show_context_menu(state, &response, image_rect);
             draw_text_editor(ui, state, image_rect);
         });
 }
@@ -127,8 +152,337 @@ fn draw_canvas_background(painter: &Painter, rect: Rect) {
 }
 
 fn draw_annotations(painter: &Painter, state: &EditorState, image_rect: Rect) {
-    for annotation in &state.annotations {
-        draw_annotation(painter, annotation, image_rect, state.zoom, false);
+    let Some(image) = state.image.as_ref() else {
+        return;
+    };
+
+    // Export applies redactions to the pixels before any shape is drawn, so
+    // the canvas paints them first as well.
+    let (redactions, shapes): (Vec<&Annotation>, Vec<&Annotation>) = state
+        .annotations
+        .iter()
+        .partition(|annotation| matches!(annotation.kind, AnnotationKind::Redact { .. }));
+    for annotation in redactions {
+        draw_annotation(painter, image, annotation, image_rect, state.zoom, false);
+    }
+    draw_spotlight(painter, state, image_rect);
+    for annotation in shapes {
+        draw_annotation(painter, image, annotation, image_rect, state.zoom, false);
+    }
+}
+
+fn active_spotlight(state: &EditorState) -> Option<Spotlight> {
+    let mut spotlight = Spotlight::from_annotations(&state.annotations);
+    if let Some(drag) = state.drag_state.as_ref() {
+        if drag.mode == DragMode::Draw && state.active_tool == Tool::Spotlight {
+            let rect = RectData {
+                min: drag.start,
+                max: drag.current,
+            }
+            .normalize();
+            spotlight
+                .get_or_insert_with(|| Spotlight {
+                    regions: Vec::new(),
+                    dim: state.active_spotlight_dim,
+                    desaturate: state.active_spotlight_desaturate,
+                })
+                .regions
+                .push((rect, state.active_spotlight_shape));
+        }
+    }
+    spotlight
+}
+
+fn draw_spotlight(painter: &Painter, state: &EditorState, image_rect: Rect) {
+    let Some(spotlight) = active_spotlight(state) else {
+        return;
+    };
+    let Some(image) = state.image.as_ref() else {
+        return;
+    };
+    let Some(texture) = image.texture.as_ref() else {
+        return;
+    };
+
+    let keep = ((1.0 - spotlight.dim) * 255.0).round() as u8;
+    match image.spotlight_gray.as_ref() {
+        Some(gray) if spotlight.desaturate => {
+            painter.image(
+                gray.texture.id(),
+                image_rect,
+                Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                Color32::from_gray(keep),
+            );
+        }
+        _ => {
+            painter.rect_filled(image_rect, 0.0, Color32::from_black_alpha(255 - keep));
+        }
+    }
+
+    for (rect, shape) in &spotlight.regions {
+        let r = rect.to_rect();
+        let min = image_to_screen(r.min, image_rect, state.zoom);
+        let max = image_to_screen(r.max, image_rect, state.zoom);
+        let screen = Rect::from_min_max(min, max);
+        let outline = match shape {
+            SpotlightShape::Rectangle => vec![
+                screen.left_top(),
+                screen.right_top(),
+                screen.right_bottom(),
+                screen.left_bottom(),
+            ],
+            SpotlightShape::Ellipse => ellipse_polyline(screen, 96),
+        };
+        paint_textured_region(painter, texture.id(), image_rect, &outline);
+        for redaction in image.redaction_previews.values() {
+            let patch = redaction_screen_rect(redaction.region, image_rect, state.zoom);
+            if patch.intersects(screen) {
+                paint_textured_region(painter, redaction.texture.id(), patch, &outline);
+            }
+        }
+    }
+}
+
+fn paint_textured_region(
+    painter: &Painter,
+    texture: TextureId,
+    texture_rect: Rect,
+    outline: &[Pos2],
+) {
+    if outline.len() < 3 || texture_rect.width() <= 0.0 || texture_rect.height() <= 0.0 {
+        return;
+    }
+    let mut mesh = Mesh::with_texture(texture);
+    for pos in outline {
+        let uv = (*pos - texture_rect.min) / texture_rect.size();
+        mesh.vertices.push(Vertex {
+            pos: *pos,
+            uv: uv.to_pos2(),
+            color: Color32::WHITE,
+        });
+    }
+    for i in 1..outline.len() as u32 - 1 {
+        mesh.add_triangle(0, i, i + 1);
+    }
+    painter.with_clip_rect(texture_rect).add(Shape::mesh(mesh));
+}
+
+fn sync_spotlight_gray(ctx: &Context, state: &mut EditorState) {
+    let needed = active_spotlight(state).is_some_and(|spotlight| spotlight.desaturate);
+    let redactions: Vec<(RectData, RedactMode, [u8; 4])> = state
+        .annotations
+        .iter()
+        .filter_map(|annotation| match &annotation.kind {
+            AnnotationKind::Redact { rect, mode } => Some((*rect, *mode, annotation.color)),
+            _ => None,
+        })
+        .collect();
+
+    let Some(image) = state.image.as_mut() else {
+        return;
+    };
+    if !needed {
+        image.spotlight_gray = None;
+        return;
+    }
+    if image
+        .spotlight_gray
+        .as_ref()
+        .is_some_and(|gray| gray.redactions == redactions)
+    {
+        return;
+    }
+
+    let mut rgba = image.dynamic.to_rgba8();
+    for (rect, mode, color) in &redactions {
+        if let Some(region) = PixelRegion::from_rect(*rect, 1.0, rgba.width(), rgba.height()) {
+            redaction::apply_redaction(&mut rgba, region, *mode, *color);
+        }
+    }
+    let gray = spotlight::desaturated(&rgba);
+    let color_image = ColorImage::from_rgba_unmultiplied(
+        [gray.width() as usize, gray.height() as usize],
+        gray.as_raw(),
+    );
+    let texture = ctx.load_texture(
+        "snapmark_spotlight_gray",
+        color_image,
+        TextureOptions::LINEAR,
+    );
+    image.spotlight_gray = Some(SpotlightGray {
+        redactions,
+        texture,
+    });
+}
+
+fn sync_text_halos(ctx: &Context, state: &mut EditorState) {
+    // Halos are rasterised in physical pixels, like the export does in output
+    // pixels, so overlapping stamps never add up.
+    let scale = state.zoom * ctx.pixels_per_point();
+    let wanted: Vec<_> = state
+        .annotations
+        .iter()
+        .filter_map(|annotation| {
+            let (size, effects) = match &annotation.kind {
+                AnnotationKind::Text { size, effects, .. }
+                | AnnotationKind::ArrowWithText { size, effects, .. } => (*size, *effects),
+                _ => return None,
+            };
+            if effects.outline_offsets(scale).is_empty() {
+                return None;
+            }
+            let layout = annotation.text_layout()?;
+            Some((annotation.id, layout, annotation.text_font(), size, effects))
+        })
+        .collect();
+
+    let Some(image) = state.image.as_mut() else {
+        return;
+    };
+    image
+        .text_halos
+        .retain(|id, _| wanted.iter().any(|(wanted_id, ..)| wanted_id == id));
+
+    for (id, layout, font, size, effects) in wanted {
+        let outline_width = effects.outline_px();
+        if image.text_halos.get(&id).is_some_and(|halo| {
+            halo.layout == layout
+                && halo.font == font
+                && halo.size == size
+                && halo.outline_width == outline_width
+                && halo.scale == scale
+        }) {
+            continue;
+        }
+        let stamp = TextStamp {
+            font: font_chain(&font),
+            font_size: size.points() * scale,
+            layout: &layout,
+            x: 0.0,
+            y: 0.0,
+            scale,
+            reach: outline_width * scale,
+        };
+        let (mask, left, top) = stamp.coverage(&effects.outline_offsets(scale));
+        let color_image = ColorImage {
+            size: [mask.width() as usize, mask.height() as usize],
+            pixels: mask
+                .pixels()
+                .map(|coverage| Color32::from_white_alpha(coverage.0[0]))
+                .collect(),
+        };
+        match image.text_halos.get_mut(&id) {
+            Some(halo) => {
+                halo.texture.set(color_image, TextureOptions::LINEAR);
+                halo.layout = layout;
+                halo.font = font;
+                halo.size = size;
+                halo.outline_width = outline_width;
+                halo.scale = scale;
+                halo.left = left;
+                halo.top = top;
+            }
+            None => {
+                let texture = ctx.load_texture(
+                    format!("snapmark_text_halo_{id}"),
+                    color_image,
+                    TextureOptions::LINEAR,
+                );
+                image.text_halos.insert(
+                    id,
+                    TextHalo {
+                        layout,
+                        font,
+                        size,
+                        outline_width,
+                        scale,
+                        left,
+                        top,
+                        texture,
+                    },
+                );
+            }
+        }
+    }
+}
+
+fn sync_redaction_previews(ctx: &Context, state: &mut EditorState) {
+    let mut wanted: Vec<(u64, RectData, RedactMode, [u8; 4])> = state
+        .annotations
+        .iter()
+        .filter_map(|annotation| match &annotation.kind {
+            AnnotationKind::Redact { rect, mode } => {
+                Some((annotation.id, *rect, *mode, annotation.color))
+            }
+            _ => None,
+        })
+        .collect();
+    if let Some(drag) = state.drag_state.as_ref() {
+        if drag.mode == DragMode::Draw && state.active_tool == Tool::Redact {
+            let rect = RectData {
+                min: drag.start,
+                max: drag.current,
+            };
+            wanted.push((0, rect, state.active_redact_mode, state.active_color));
+        }
+    }
+
+    let Some(image) = state.image.as_mut() else {
+        return;
+    };
+    image
+        .redaction_previews
+        .retain(|id, _| wanted.iter().any(|(wanted_id, ..)| wanted_id == id));
+
+    let mut source = None;
+    for (id, rect, mode, color) in wanted {
+        if image.redaction_previews.get(&id).is_some_and(|preview| {
+            preview.rect == rect && preview.mode == mode && preview.color == color
+        }) {
+            continue;
+        }
+        let Some(region) =
+            PixelRegion::from_rect(rect, 1.0, image.dynamic.width(), image.dynamic.height())
+        else {
+            image.redaction_previews.remove(&id);
+            continue;
+        };
+
+        let rgba = source.get_or_insert_with(|| match image.dynamic.as_rgba8() {
+            Some(rgba) => Cow::Borrowed(rgba),
+            None => Cow::Owned(image.dynamic.to_rgba8()),
+        });
+        let patch = redaction::redacted_patch(rgba, region, mode, color);
+        let color_image = ColorImage::from_rgba_unmultiplied(
+            [patch.width() as usize, patch.height() as usize],
+            patch.as_raw(),
+        );
+        match image.redaction_previews.get_mut(&id) {
+            Some(preview) => {
+                preview.texture.set(color_image, TextureOptions::NEAREST);
+                preview.rect = rect;
+                preview.mode = mode;
+                preview.color = color;
+                preview.region = region;
+            }
+            None => {
+                let texture = ctx.load_texture(
+                    format!("snapmark_redaction_{id}"),
+                    color_image,
+                    TextureOptions::NEAREST,
+                );
+                image.redaction_previews.insert(
+                    id,
+                    RedactionPreview {
+                        rect,
+                        mode,
+                        color,
+                        region,
+                        texture,
+                    },
+                );
+            }
+        }
     }
 }
 
@@ -137,6 +491,15 @@ fn draw_drag_preview(painter: &Painter, state: &EditorState, image_rect: Rect) {
         return;
     };
 
+    if drag.mode == DragMode::Marquee {
+        let band = Rect::from_two_pos(
+            image_to_screen(drag.start.to_pos2(), image_rect, state.zoom),
+            image_to_screen(drag.current.to_pos2(), image_rect, state.zoom),
+        );
+        painter.rect_filled(band, 0.0, Color32::from_rgba_unmultiplied(77, 141, 255, 30));
+        painter.rect_stroke(band, 0.0, Stroke::new(1.0, Color32::from_rgb(77, 141, 255)));
+        return;
+    }
     if drag.mode != DragMode::Draw {
         return;
     }
@@ -147,9 +510,14 @@ fn draw_drag_preview(painter: &Painter, state: &EditorState, image_rect: Rect) {
             kind: AnnotationKind::Arrow {
                 from: drag.start,
                 to: drag.current,
+                head: state.active_arrow_head,
+                curve: None,
             },
             color: state.active_color,
             stroke_width: state.active_stroke,
+            stroke_style: state.active_stroke_style,
+            rotation: 0.0,
+            group: None,
         },
         Tool::Rectangle => Annotation {
             id: 0,
@@ -158,9 +526,14 @@ fn draw_drag_preview(painter: &Painter, state: &EditorState, image_rect: Rect) {
                     min: drag.start,
                     max: drag.current,
                 },
+                fill: state.active_fill(),
+                corner_radius: 0.0,
             },
             color: state.active_color,
             stroke_width: state.active_stroke,
+            stroke_style: state.active_stroke_style,
+            rotation: 0.0,
+            group: None,
         },
         Tool::Ellipse => Annotation {
             id: 0,
@@ -169,52 +542,232 @@ fn draw_drag_preview(painter: &Painter, state: &EditorState, image_rect: Rect) {
                     min: drag.start,
                     max: drag.current,
                 },
+                fill: state.active_fill(),
+            },
+            color: state.active_color,
+            stroke_width: state.active_stroke,
+            stroke_style: state.active_stroke_style,
+            rotation: 0.0,
+            group: None,
+        },
+        Tool::Freehand => Annotation {
+            id: 0,
+            kind: AnnotationKind::Freehand {
+                points: drag.points.clone(),
+            },
+            color: state.active_color,
+            stroke_width: state.active_stroke,
+            stroke_style: state.active_stroke_style,
+            rotation: 0.0,
+            group: None,
+        },
+        Tool::Highlighter => Annotation {
+            id: 0,
+            kind: AnnotationKind::Highlighter {
+                points: drag.points.clone(),
+            },
+            color: state.active_highlight_color,
+            stroke_width: state.active_stroke,
+            stroke_style: state.active_stroke_style,
+            rotation: 0.0,
+            group: None,
+        },
+        Tool::Callout => {
+            let rect = RectData {
+                min: drag.start,
+                max: drag.current,
+            }
+            .normalize();
+            Annotation {
+                id: 0,
+                kind: AnnotationKind::Callout {
+                    rect,
+                    tail: default_callout_tail(rect),
+                    text: String::new(),
+                    size: state.active_text_size,
+                },
+                color: state.active_color,
+                stroke_width: state.active_stroke,
+                stroke_style: state.active_stroke_style,
+                rotation: 0.0,
+                group: None,
+            }
+        }
+        Tool::Magnifier => {
+            let Some(image) = state.image.as_ref() else {
+                return;
+            };
+            let source = RectData {
+                min: drag.start,
+                max: drag.current,
+            }
+            .normalize();
+            Annotation {
+                id: 0,
+                kind: AnnotationKind::Magnifier {
+                    source,
+                    target: default_loupe_target(source, image.size_vec2()),
+                    shape: state.active_loupe_shape,
+                    connector: state.active_loupe_connector,
+                },
+                color: state.active_color,
+                stroke_width: state.active_stroke,
+                stroke_style: state.active_stroke_style,
+                rotation: 0.0,
+                group: None,
+            }
+        }
+        Tool::Redact => Annotation {
+            id: 0,
+            kind: AnnotationKind::Redact {
+                rect: RectData {
+                    min: drag.start,
+                    max: drag.current,
+                },
+                mode: state.active_redact_mode,
+            },
+            color: state.active_color,
+            stroke_width: state.active_stroke,
+            stroke_style: state.active_stroke_style,
+            rotation: 0.0,
+            group: None,
+        },
+        Tool::Stamp => Annotation {
+            id: 0,
+            kind: AnnotationKind::Stamp {
+                rect: RectData {
+                    min: drag.start,
+                    max: drag.current,
+                },
+                icon: state.active_stamp,
             },
             color: state.active_color,
             stroke_width: state.active_stroke,
+            stroke_style: state.active_stroke_style,
+            rotation: 0.0,
+            group: None,
         },
         _ => return,
     };
 
-    draw_annotation(painter, &preview, image_rect, state.zoom, true);
+    let Some(image) = state.image.as_ref() else {
+        return;
+    };
+    draw_annotation(painter, image, &preview, image_rect, state.zoom, true);
 }
 
 fn draw_selection(painter: &Painter, state: &EditorState, image_rect: Rect) {
-    let Some(selected_id) = state.selection else {
-        return;
-    };
-    let Some(annotation) = state.annotations.iter().find(|item| item.id == selected_id) else {
-        return;
-    };
+    let single = state.selection.len() == 1;
+    let whole_group = state.selected_group();
+    let mut framed_groups = Vec::new();
+    for annotation in &state.annotations {
+        if !state.is_selected(annotation.id) {
+            continue;
+        }
+        // Grouped annotations share one frame around the whole group.
+        match annotation.group {
+            Some(group) if !framed_groups.contains(&group) => {
+                framed_groups.push(group);
+                if let Some(bounds) = state.group_bounds(group) {
+                    draw_group_frame(
+                        painter,
+                        bounds,
+                        image_rect,
+                        state.zoom,
+                        whole_group == Some(group),
+                    );
+                }
+            }
+            Some(_) => {}
+            None => draw_selection_frame(painter, annotation, image_rect, state.zoom, single),
+        }
+    }
+}
 
-    let bounds = annotation.bounds();
-    let min = image_to_screen(bounds.min, image_rect, state.zoom);
-    let max = image_to_screen(bounds.max, image_rect, state.zoom);
-    let selection_rect = Rect::from_min_max(min, max);
+fn draw_group_frame(
+    painter: &Painter,
+    bounds: Rect,
+    image_rect: Rect,
+    zoom: f32,
+    with_handles: bool,
+) {
+    let accent = Color32::from_rgb(77, 141, 255);
+    let min = image_to_screen(bounds.min, image_rect, zoom);
+    let max = image_to_screen(bounds.max, image_rect, zoom);
+    painter.rect_stroke(Rect::from_min_max(min, max), 8.0, Stroke::new(1.8, accent));
+    if !with_handles {
+        return;
+    }
+    for (_, point) in RectData::from_rect(bounds).handles() {
+        draw_handle_square(painter, image_to_screen(point.to_pos2(), image_rect, zoom));
+    }
+}
 
+fn draw_handle_square(painter: &Painter, handle_pos: Pos2) {
+    painter.rect_filled(
+        Rect::from_center_size(handle_pos, vec2(9.0, 9.0)),
+        4.0,
+        Color32::from_rgb(77, 141, 255),
+    );
     painter.rect_stroke(
-        selection_rect,
-        8.0,
-        Stroke::new(1.8, Color32::from_rgb(77, 141, 255)),
+        Rect::from_center_size(handle_pos, vec2(9.0, 9.0)),
+        4.0,
+        Stroke::new(1.0, Color32::from_rgba_unmultiplied(255, 255, 255, 200)),
     );
+}
 
-    for (_, point) in annotation.handles() {
-        let handle_pos = image_to_screen(point.to_pos2(), image_rect, state.zoom);
-        painter.rect_filled(
-            Rect::from_center_size(handle_pos, vec2(9.0, 9.0)),
-            4.0,
-            Color32::from_rgb(77, 141, 255),
-        );
-        painter.rect_stroke(
-            Rect::from_center_size(handle_pos, vec2(9.0, 9.0)),
-            4.0,
-            Stroke::new(1.0, Color32::from_rgba_unmultiplied(255, 255, 255, 200)),
-        );
+fn draw_selection_frame(
+    painter: &Painter,
+    annotation: &Annotation,
+    image_rect: Rect,
+    zoom: f32,
+    with_handles: bool,
+) {
+    let accent = Color32::from_rgb(77, 141, 255);
+    // Rotated shapes are framed by their upright box, turned with them.
+    let turn = screen_turn(annotation, image_rect, zoom);
+    let bounds = if turn.angle == 0.0 {
+        annotation.bounds()
+    } else {
+        annotation.local_bounds()
+    };
+    let min = image_to_screen(bounds.min, image_rect, zoom);
+    let max = image_to_screen(bounds.max, image_rect, zoom);
+    let selection_rect = Rect::from_min_max(min, max);
+
+    if turn.angle == 0.0 {
+        painter.rect_stroke(selection_rect, 8.0, Stroke::new(1.8, accent));
+    } else {
+        let frame = rounded_rect_outline(selection_rect, 8.0)
+            .into_iter()
+            .map(|point| turn.apply(point))
+            .collect();
+        painter.add(Shape::closed_line(frame, Stroke::new(1.8, accent)));
+    }
+
+    if !with_handles {
+        return;
+    }
+    for (handle, point) in annotation.handles(zoom) {
+        let handle_pos = image_to_screen(point.to_pos2(), image_rect, zoom);
+        if handle == Handle::Rotate {
+            let stem = turn.apply(selection_rect.center_top());
+            painter.line_segment([stem, handle_pos], Stroke::new(1.2, accent));
+            painter.circle_filled(handle_pos, 5.0, accent);
+            painter.circle_stroke(
+                handle_pos,
+                5.0,
+                Stroke::new(1.0, Color32::from_rgba_unmultiplied(255, 255, 255, 200)),
+            );
+            continue;
+        }
+        draw_handle_square(painter, handle_pos);
     }
 }
 
 fn draw_annotation(
     painter: &Painter,
+    image: &EditorImage,
     annotation: &Annotation,
     image_rect: Rect,
     zoom: f32,
@@ -226,83 +779,460 @@ fn draw_annotation(
     }
 
     let stroke = Stroke::new((annotation.stroke_width.px() * zoom).max(1.0), color);
+    // Rotated shapes are built upright on screen, then turned about the
+    // screen position of their pivot.
+    let screen_turn = screen_turn(annotation, image_rect, zoom);
+    let turned = |points: Vec<Pos2>| -> Vec<Pos2> {
+        points
+            .into_iter()
+            .map(|point| screen_turn.apply(point))
+            .collect()
+    };
 
     match &annotation.kind {
-        AnnotationKind::Arrow { from, to } => {
-            draw_arrow(painter, *from, *to, image_rect, zoom, stroke)
-        }
+        AnnotationKind::Arrow {
+            from,
+            to,
+            head,
+            curve,
+        } => draw_arrow(
+            painter,
+            *from,
+            *to,
+            *head,
+            *curve,
+            image_rect,
+            zoom,
+            stroke,
+            annotation.stroke_style,
+        ),
         AnnotationKind::ArrowWithText {
             from,
             to,
-            text,
             size,
+            head,
+            curve,
+            effects,
+            ..
         } => {
-            draw_arrow(painter, *from, *to, image_rect, zoom, stroke);
-            let anchor = arrow_text_anchor(*from, *to);
-            let pos = image_to_screen(anchor.to_pos2(), image_rect, zoom);
-            painter.text(
-                pos,
-                Align2::LEFT_TOP,
-                text,
-                FontId::proportional(size.points() * zoom.min(1.25)),
-                color,
+            draw_arrow(
+                painter,
+                *from,
+                *to,
+                *head,
+                *curve,
+                image_rect,
+                zoom,
+                stroke,
+                annotation.stroke_style,
+            );
+            draw_annotation_text(
+                painter, image, annotation, *size, effects, image_rect, zoom, color, preview,
             );
         }
-        AnnotationKind::Text { pos, content, size } => {
-            let screen = image_to_screen(pos.to_pos2(), image_rect, zoom);
-            painter.text(
-                screen,
-                Align2::LEFT_TOP,
-                content,
-                FontId::proportional(size.points() * zoom.min(1.25)),
-                color,
+        AnnotationKind::Text { size, effects, .. } => {
+            draw_annotation_text(
+                painter, image, annotation, *size, effects, image_rect, zoom, color, preview,
             );
         }
-        AnnotationKind::Rectangle { rect } => {
+        AnnotationKind::Rectangle {
+            rect,
+            fill,
+            corner_radius,
+        } => {
             let r = rect.normalize().to_rect();
             let min = image_to_screen(r.min, image_rect, zoom);
             let max = image_to_screen(r.max, image_rect, zoom);
-            painter.rect_stroke(Rect::from_min_max(min, max), 0.0, stroke);
+            let screen = Rect::from_min_max(min, max);
+            let points = turned(if *corner_radius > 0.0 {
+                rounded_rect_outline(screen, corner_radius * zoom)
+            } else {
+                vec![
+                    screen.left_top(),
+                    screen.right_top(),
+                    screen.right_bottom(),
+                    screen.left_bottom(),
+                ]
+            });
+            if let Some(fill) = fill_color32(*fill, preview) {
+                painter.add(Shape::convex_polygon(points.clone(), fill, Stroke::NONE));
+            }
+            stroke_outline(painter, points, true, stroke, annotation.stroke_style);
         }
-        AnnotationKind::Ellipse { rect } => {
+        AnnotationKind::Ellipse { rect, fill } => {
             let r = rect.normalize().to_rect();
             let min = image_to_screen(r.min, image_rect, zoom);
             let max = image_to_screen(r.max, image_rect, zoom);
-            let points = ellipse_polyline(Rect::from_min_max(min, max), 56);
+            let points = turned(ellipse_polyline(Rect::from_min_max(min, max), 56));
+            if let Some(fill) = fill_color32(*fill, preview) {
+                painter.add(Shape::convex_polygon(points.clone(), fill, Stroke::NONE));
+            }
+            stroke_outline(painter, points, true, stroke, annotation.stroke_style);
+        }
+        AnnotationKind::Freehand { points } => {
+            let screen: Vec<Pos2> = points
+                .iter()
+                .map(|point| image_to_screen(point.to_pos2(), image_rect, zoom))
+                .collect();
+            match screen.as_slice() {
+                [] => {}
+                [single] => {
+                    painter.circle_filled(*single, stroke.width * 0.5, stroke.color);
+                }
+                _ => {
+                    stroke_outline(painter, screen, false, stroke, annotation.stroke_style);
+                }
+            }
+        }
+        AnnotationKind::Spotlight { .. } => {
+            // Drawn as part of the shared spotlight mask.
+        }
+        AnnotationKind::Stamp { rect, icon } => {
+            let r = rect.normalize().to_rect();
+            let screen = Rect::from_min_max(
+                image_to_screen(r.min, image_rect, zoom),
+                image_to_screen(r.max, image_rect, zoom),
+            );
+            let [cr, cg, cb, ca] = annotation.contrast_text_color();
+            let mut contrast = Color32::from_rgba_unmultiplied(cr, cg, cb, ca);
+            if preview {
+                contrast = contrast.linear_multiply(0.7);
+            }
+            paint_stamp(painter, *icon, screen, screen_turn, color, contrast);
+        }
+        AnnotationKind::Magnifier {
+            source,
+            target,
+            shape,
+            connector,
+        } => {
+            let from = source.to_rect();
+            let to = target.to_rect();
+            if from.width() < 1.0 || from.height() < 1.0 {
+                return;
+            }
+            let to_screen = |r: Rect| {
+                Rect::from_min_max(
+                    image_to_screen(r.min, image_rect, zoom),
+                    image_to_screen(r.max, image_rect, zoom),
+                )
+            };
+            let frame = loupe_outline(to_screen(to), *shape);
+
+            // Lay the photo out at the loupe's magnification so that the
+            // source region lands exactly on the frame, then fill the frame
+            // from it; redaction patches get the same mapping.
+            let magnification = to.size() / from.size();
+            let magnified = |r: Rect| {
+                to_screen(Rect::from_min_max(
+                    to.min + (r.min - from.min) * magnification,
+                    to.min + (r.max - from.min) * magnification,
+                ))
+            };
+            if let Some(texture) = image.texture.as_ref() {
+                let photo = Rect::from_min_size(Pos2::ZERO, image.size_vec2());
+                paint_textured_region(painter, texture.id(), magnified(photo), &frame);
+            }
+            for redaction in image.redaction_previews.values() {
+                let region = redaction.region;
+                let patch = Rect::from_min_size(
+                    Pos2::new(region.x as f32, region.y as f32),
+                    vec2(region.width as f32, region.height as f32),
+                );
+                if patch.intersects(from) {
+                    paint_textured_region(
+                        painter,
+                        redaction.texture.id(),
+                        magnified(patch),
+                        &frame,
+                    );
+                }
+            }
+
+            painter.add(Shape::closed_line(frame, stroke));
+            painter.add(Shape::closed_line(
+                loupe_outline(to_screen(from), *shape),
+                stroke,
+            ));
+            if *connector {
+                if let Some((start, end)) = magnifier_connector(*source, *target, *shape) {
+                    painter.line_segment(
+                        [
+                            image_to_screen(start.to_pos2(), image_rect, zoom),
+                            image_to_screen(end.to_pos2(), image_rect, zoom),
+                        ],
+                        stroke,
+                    );
+                }
+            }
+        }
+        AnnotationKind::Redact { .. } => {
+            let Some(redaction) = image.redaction_previews.get(&annotation.id) else {
+                return;
+            };
+            let screen_rect = redaction_screen_rect(redaction.region, image_rect, zoom);
+            painter.image(
+                redaction.texture.id(),
+                screen_rect,
+                Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                Color32::WHITE,
+            );
+            if preview {
+                painter.rect_stroke(screen_rect, 0.0, Stroke::new(1.0, color));
+            }
+        }
+        AnnotationKind::Step { center, size, .. } => {
+            let screen = image_to_screen(center.to_pos2(), image_rect, zoom);
+            let [r, g, b, a] = annotation.contrast_text_color();
+            painter.circle_filled(screen, step_radius(*size) * zoom, color);
+            draw_annotation_text(
+                painter,
+                image,
+                annotation,
+                *size,
+                &TextEffects::default(),
+                image_rect,
+                zoom,
+                Color32::from_rgba_unmultiplied(r, g, b, a),
+                false,
+            );
+        }
+        AnnotationKind::Callout {
+            rect, tail, size, ..
+        } => {
+            let r = rect.to_rect();
+            let screen_rect = Rect::from_min_max(
+                image_to_screen(r.min, image_rect, zoom),
+                image_to_screen(r.max, image_rect, zoom),
+            );
+            let (base_a, base_b) = callout_tail_base(*rect, *tail);
+            painter.add(Shape::convex_polygon(
+                turned(vec![
+                    image_to_screen(base_a.to_pos2(), image_rect, zoom),
+                    image_to_screen(tail.to_pos2(), image_rect, zoom),
+                    image_to_screen(base_b.to_pos2(), image_rect, zoom),
+                ]),
+                color,
+                Stroke::NONE,
+            ));
+            painter.add(Shape::convex_polygon(
+                turned(rounded_rect_outline(screen_rect, CALLOUT_ROUNDING * zoom)),
+                color,
+                Stroke::NONE,
+            ));
+
+            let [tr, tg, tb, ta] = annotation.contrast_text_color();
+            draw_annotation_text(
+                painter,
+                image,
+                annotation,
+                *size,
+                &TextEffects::default(),
+                image_rect,
+                zoom,
+                Color32::from_rgba_unmultiplied(tr, tg, tb, ta),
+                false,
+            );
+        }
+        AnnotationKind::Highlighter { points } => {
+            // egui has no multiply blending, so the preview approximates the
+            // exported marker with a translucent band.
+            let band = Color32::from_rgba_unmultiplied(
+                annotation.color[0],
+                annotation.color[1],
+                annotation.color[2],
+                (annotation.color[3] as f32 * if preview { 0.3 } else { 0.45 }) as u8,
+            );
+            let screen: Vec<Pos2> = points
+                .iter()
+                .map(|point| image_to_screen(point.to_pos2(), image_rect, zoom))
+                .collect();
+            if screen.len() >= 2 {
+                painter.add(Shape::line(
+                    screen,
+                    Stroke::new((annotation.line_width() * zoom).max(1.0), band),
+                ));
+            }
+        }
+        AnnotationKind::Polygon {
+            points,
+            closed,
+            filled,
+        } => {
+            let screen: Vec<Pos2> = points
+                .iter()
+                .map(|point| screen_turn.apply(image_to_screen(point.to_pos2(), image_rect, zoom)))
+                .collect();
+            if *closed && *filled {
+                let [r, g, b, a] = annotation.polygon_fill_color();
+                let mut fill = Color32::from_rgba_unmultiplied(r, g, b, a);
+                if preview {
+                    fill = fill.linear_multiply(0.7);
+                }
+                let mut mesh = Mesh::default();
+                for triangle in triangulate_polygon(points) {
+                    let base = mesh.vertices.len() as u32;
+                    for point in triangle {
+                        let pos = image_to_screen(point.to_pos2(), image_rect, zoom);
+                        mesh.colored_vertex(screen_turn.apply(pos), fill);
+                    }
+                    mesh.add_triangle(base, base + 1, base + 2);
+                }
+                painter.add(Shape::mesh(mesh));
+            }
+            if screen.len() >= 2 {
+                let closed = *closed && screen.len() >= 3;
+                stroke_outline(painter, screen, closed, stroke, annotation.stroke_style);
+            }
+        }
+    }
+}
+
+fn draw_polygon_draft(painter: &Painter, state: &EditorState, image_rect: Rect) {
+    if state.active_tool != Tool::Polygon || state.polygon_draft.is_empty() {
+        return;
+    }
+    let Some(image) = state.image.as_ref() else {
+        return;
+    };
+
+    let draft = Annotation {
+        id: 0,
+        kind: AnnotationKind::Polygon {
+            points: state.polygon_draft.clone(),
+            closed: false,
+            filled: false,
+        },
+        color: state.active_color,
+        stroke_width: state.active_stroke,
+        stroke_style: state.active_stroke_style,
+        rotation: 0.0,
+        group: None,
+    };
+    draw_annotation(painter, image, &draft, image_rect, state.zoom, true);
+
+    let color = draft.color32().linear_multiply(0.7);
+    let stroke = Stroke::new((draft.stroke_width.px() * state.zoom).max(1.0), color);
+    let last = image_to_screen(
+        state.polygon_draft[state.polygon_draft.len() - 1].to_pos2(),
+        image_rect,
+        state.zoom,
+    );
+    let first = image_to_screen(state.polygon_draft[0].to_pos2(), image_rect, state.zoom);
+    if let Some(hover) = painter.ctx().pointer_hover_pos() {
+        painter.add(Shape::dashed_line(&[last, hover], stroke, 6.0, 4.0));
+        if state.polygon_draft.len() >= 3 && hover.distance(first) <= POLYGON_CLOSE_RADIUS {
+            painter.circle_stroke(first, POLYGON_CLOSE_RADIUS, Stroke::new(1.5, color));
+        }
+    }
+    for point in &state.polygon_draft {
+        let pos = image_to_screen(point.to_pos2(), image_rect, state.zoom);
+        painter.circle_filled(pos, 3.5, color);
+    }
+}
+
+fn stroke_outline(
+    painter: &Painter,
+    mut points: Vec<Pos2>,
+    closed: bool,
+    stroke: Stroke,
+    style: StrokeStyle,
+) {
+    match style.dash_pattern(stroke.width) {
+        None if closed => {
             painter.add(Shape::closed_line(points, stroke));
         }
+        None => {
+            painter.add(Shape::line(points, stroke));
+        }
+        Some([on, off]) => {
+            if closed {
+                if let Some(first) = points.first().copied() {
+                    points.push(first);
+                }
+            }
+            if style == StrokeStyle::Dotted {
+                painter.extend(Shape::dotted_line(
+                    &points,
+                    stroke.color,
+                    on + off,
+                    stroke.width * 0.5,
+                ));
+            } else {
+                painter.extend(Shape::dashed_line(&points, stroke, on, off));
+            }
+        }
     }
 }
 
+const POLYGON_CLOSE_RADIUS: f32 = 8.0;
+
+#[allow(clippy::too_many_arguments)]
 fn draw_arrow(
     painter: &Painter,
     from: Point,
     to: Point,
+    head: ArrowHead,
+    curve: Option<ArrowCurve>,
     image_rect: Rect,
     zoom: f32,
     stroke: Stroke,
+    style: StrokeStyle,
 ) {
+    let points: Vec<Pos2> = arrow_polyline(from, to, curve)
+        .into_iter()
+        .map(|point| image_to_screen(point.to_pos2(), image_rect, zoom))
+        .collect();
+    stroke_outline(painter, points, false, stroke, style);
+
+    // Heads follow the curve tangent, so aim each one from a point just
+    // behind its tip along that tangent.
     let from_screen = image_to_screen(from.to_pos2(), image_rect, zoom);
     let to_screen = image_to_screen(to.to_pos2(), image_rect, zoom);
-    painter.line_segment([from_screen, to_screen], stroke);
+    let (start_dir, end_dir) = arrow_end_directions(from, to, curve);
+    let behind_to = to_screen + end_dir.normalized();
+    let behind_from = from_screen + start_dir.normalized();
+
+    match head {
+        ArrowHead::None => {}
+        ArrowHead::Open | ArrowHead::Filled | ArrowHead::Dot => {
+            draw_arrow_end(painter, behind_to, to_screen, head, stroke);
+        }
+        ArrowHead::Double => {
+            draw_arrow_end(painter, behind_to, to_screen, ArrowHead::Filled, stroke);
+            draw_arrow_end(painter, behind_from, from_screen, ArrowHead::Filled, stroke);
+        }
+    }
+}
 
-    let direction = to_screen - from_screen;
+fn draw_arrow_end(painter: &Painter, from: Pos2, tip: Pos2, head: ArrowHead, stroke: Stroke) {
+    let direction = tip - from;
     let len = direction.length().max(1.0);
     let unit = direction / len;
     let head_length = 12.0;
     let head_half_width = 7.0;
 
-    let tip = to_screen;
     let base = tip - unit * head_length;
     let normal = vec2(-unit.y, unit.x);
     let left = base + normal * head_half_width;
     let right = base - normal * head_half_width;
 
-    painter.add(Shape::convex_polygon(
-        vec![tip, left, right],
-        stroke.color,
-        Stroke::NONE,
-    ));
+    match head {
+        ArrowHead::Filled | ArrowHead::Double => {
+            painter.add(Shape::convex_polygon(
+                vec![tip, left, right],
+                stroke.color,
+                Stroke::NONE,
+            ));
+        }
+        ArrowHead::Open => {
+            painter.add(Shape::line(vec![left, tip, right], stroke));
+        }
+        ArrowHead::Dot => {
+            painter.circle_filled(tip, (stroke.width * 1.5).max(4.0), stroke.color);
+        }
+        ArrowHead::None => {}
+    }
 }
 
 fn handle_pointer_interaction(
@@ -330,7 +1260,12 @@ fn handle_pointer_interaction(
     let image_pos = screen_to_image(pointer_pos, image_rect, state.zoom);
 
     if response.double_clicked() {
-        handle_double_click(state, image_pos, pointer_pos);
+        if state.active_tool == Tool::Polygon {
+            let keep_open = ctx.input(|input| input.modifiers.shift);
+            state.finish_polygon(!keep_open);
+        } else {
+            handle_double_click(state, image_pos, pointer_pos);
+        }
         return true;
     }
 
@@ -353,6 +1288,69 @@ fn handle_pointer_interaction(
     true
 }
 
+fn show_context_menu(state: &mut EditorState, response: &Response, image_rect: Rect) {
+    if response.secondary_clicked() {
+        if let Some(pointer_pos) = response.interact_pointer_pos() {
+            let image_pos = screen_to_image(pointer_pos, image_rect, state.zoom);
+            if let Some(hit_id) = pick_annotation(state, image_pos) {
+                if !state.is_selected(hit_id) {
+                    state.select(Some(hit_id));
+                }
+            }
+        }
+    }
+    if state.selection.is_empty() {
+        return;
+    }
+    response.context_menu(|ui| {
+        for (label, shortcut, order) in [
+            ("Bring to Front", "Shift+Cmd+]", ZOrder::BringToFront),
+            ("Bring Forward", "Cmd+]", ZOrder::BringForward),
+            ("Send Backward", "Cmd+[", ZOrder::SendBackward),
+            ("Send to Back", "Shift+Cmd+[", ZOrder::SendToBack),
+        ] {
+            if ui.add(Button::new(label).shortcut_text(shortcut)).clicked() {
+                state.reorder_selected(order);
+                ui.close_menu();
+            }
+        }
+
+        let pieces = state.selected_units().len();
+        if pieces < 2 {
+            return;
+        }
+        ui.separator();
+        ui.menu_button("Align", |ui| {
+            for (label, edge) in [
+                ("Left", AlignEdge::Left),
+                ("Centre", AlignEdge::Center),
+                ("Right", AlignEdge::Right),
+                ("Top", AlignEdge::Top),
+                ("Middle", AlignEdge::Middle),
+                ("Bottom", AlignEdge::Bottom),
+            ] {
+                if ui.button(label).clicked() {
+                    state.align_selected(edge);
+                    ui.close_menu();
+                }
+            }
+        });
+        ui.add_enabled_ui(pieces >= 3, |ui| {
+            ui.menu_button("Distribute", |ui| {
+                for (label, axis) in [
+                    ("Horizontally", Distribute::Horizontally),
+                    ("Vertically", Distribute::Vertically),
+                ] {
+                    if ui.button(label).clicked() {
+                        state.distribute_selected(axis);
+                        ui.close_menu();
+                    }
+                }
+            });
+        });
+    });
+}
+
 fn begin_drag(
     ctx: &Context,
     state: &mut EditorState,
@@ -361,54 +1359,118 @@ fn begin_drag(
     image_rect: Rect,
 ) {
     match state.active_tool {
-        Tool::Arrow | Tool::ArrowWithText | Tool::Rectangle | Tool::Ellipse => {
+        Tool::Arrow
+        | Tool::ArrowWithText
+        | Tool::Rectangle
+        | Tool::Ellipse
+        | Tool::Freehand
+        | Tool::Highlighter
+        | Tool::Redact
+        | Tool::Spotlight
+        | Tool::Magnifier
+        | Tool::Callout
+        | Tool::Stamp => {
             state.drag_state = Some(DragState {
                 mode: DragMode::Draw,
                 start: image_pos,
                 current: image_pos,
                 selection_id: None,
                 handle: None,
-                original: None,
+                originals: Vec::new(),
+                points: vec![image_pos],
             });
         }
         Tool::Select => {
-            if let Some(selected_id) = state.selection {
+            // Handles are only offered while a single annotation is selected.
+            if let Some(selected) = state.single_selection().cloned() {
                 if let Some((handle, _)) =
-                    detect_handle_hit(state, selected_id, image_pos, image_rect)
+                    detect_handle_hit(state, selected.id, image_pos, image_rect)
                 {
-                    let original = state
+                    state.drag_state = Some(DragState {
+                        mode: DragMode::Resize,
+                        start: image_pos,
+                        current: image_pos,
+                        selection_id: Some(selected.id),
+                        handle: Some(handle),
+                        originals: vec![selected],
+                        points: Vec::new(),
+                    });
+                    return;
+                }
+            }
+            // A selected group is resized as one box.
+            if let Some(bounds) = state
+                .selected_group()
+                .and_then(|group| state.group_bounds(group))
+            {
+                let handles = RectData::from_rect(bounds).handles();
+                if let Some((handle, _)) = hit_handle(state, handles, image_pos, image_rect) {
+                    let originals = state
                         .annotations
                         .iter()
-                        .find(|a| a.id == selected_id)
-                        .cloned();
+                        .filter(|annotation| state.is_selected(annotation.id))
+                        .cloned()
+                        .collect();
                     state.drag_state = Some(DragState {
                         mode: DragMode::Resize,
                         start: image_pos,
                         current: image_pos,
-                        selection_id: Some(selected_id),
+                        selection_id: None,
                         handle: Some(handle),
-                        original,
+                        originals,
+                        points: Vec::new(),
                     });
                     return;
                 }
             }
 
+            let additive = ctx.input(|input| input.modifiers.shift);
             if let Some(hit_id) = pick_annotation(state, image_pos) {
-                state.selection = Some(hit_id);
-                let original = state.annotations.iter().find(|a| a.id == hit_id).cloned();
+                // Dragging any selected annotation moves the whole set.
+                if !state.is_selected(hit_id) {
+                    if !additive {
+                        state.selection.clear();
+                    }
+                    state.add_to_selection(hit_id);
+                }
+                let originals = state
+                    .annotations
+                    .iter()
+                    .filter(|annotation| state.is_selected(annotation.id))
+                    .cloned()
+                    .collect();
                 state.drag_state = Some(DragState {
                     mode: DragMode::Move,
                     start: image_pos,
                     current: image_pos,
                     selection_id: Some(hit_id),
                     handle: None,
-                    original,
+                    originals,
+                    points: Vec::new(),
                 });
             } else {
-                state.selection = None;
+                if !additive {
+                    state.selection.clear();
+                }
+                // The band starts where the button went down, not where the
+                // drag was recognised.
+                let start = ctx
+                    .input(|input| input.pointer.press_origin())
+                    .map_or(image_pos, |origin| {
+                        screen_to_image(origin, image_rect, state.zoom)
+                    });
+                state.drag_state = Some(DragState {
+                    mode: DragMode::Marquee,
+                    start,
+                    current: image_pos,
+                    selection_id: None,
+                    handle: None,
+                    originals: Vec::new(),
+                    points: Vec::new(),
+                });
             }
         }
-        Tool::Text => {
+        Tool::Text | Tool::Step | Tool::Polygon => {
             let _ = image_rect;
         }
     }
@@ -418,7 +1480,7 @@ fn begin_drag(
 
 fn update_drag(ctx: &Context, state: &mut EditorState, image_pos: Point) {
     let active_tool = state.active_tool;
-    let (mode, start, selection_id, handle, original, tool) = {
+    let (mode, start, selection_id, handle, originals, tool) = {
         let Some(drag) = state.drag_state.as_mut() else {
             return;
         };
@@ -428,14 +1490,29 @@ fn update_drag(ctx: &Context, state: &mut EditorState, image_pos: Point) {
             drag.start,
             drag.selection_id,
             drag.handle,
-            drag.original.clone(),
+            drag.originals.clone(),
             active_tool,
         )
     };
 
     match mode {
         DragMode::Draw => {
-            if matches!(tool, Tool::Rectangle | Tool::Ellipse)
+            if tool == Tool::Freehand {
+                if let Some(drag) = state.drag_state.as_mut() {
+                    drag.points.push(image_pos);
+                }
+            }
+            if tool == Tool::Highlighter {
+                let straight = ctx.input(|input| input.modifiers.shift);
+                if let Some(drag) = state.drag_state.as_mut() {
+                    if straight {
+                        drag.points = vec![drag.start, image_pos];
+                    } else {
+                        drag.points.push(image_pos);
+                    }
+                }
+            }
+            if matches!(tool, Tool::Rectangle | Tool::Ellipse | Tool::Spotlight)
                 && ctx.input(|input| input.modifiers.shift)
             {
                 if let Some(drag) = state.drag_state.as_mut() {
@@ -443,27 +1520,97 @@ fn update_drag(ctx: &Context, state: &mut EditorState, image_pos: Point) {
                 }
             }
         }
+        DragMode::Marquee => {
+            // The band follows `drag.current`; it selects on release.
+        }
         DragMode::Move => {
-            if let (Some(id), Some(original)) = (selection_id, original) {
-                let delta = start.delta(image_pos);
-                if let Some(annotation) = state.find_annotation_mut(id) {
+            let delta = start.delta(image_pos);
+            for original in originals {
+                if let Some(annotation) = state.find_annotation_mut(original.id) {
                     *annotation = original;
                     annotation.move_by(delta);
                 }
             }
         }
-        DragMode::Resize => {
-            if let (Some(id), Some(handle), Some(original)) = (selection_id, handle, original) {
+        DragMode::Resize if selection_id.is_none() => {
+            if let Some(handle) = handle {
                 let keep_square = ctx.input(|input| input.modifiers.shift);
+                resize_group(state, &originals, handle, image_pos, keep_square);
+            }
+        }
+        DragMode::Resize => {
+            if let (Some(id), Some(handle), Some(original)) =
+                (selection_id, handle, originals.into_iter().next())
+            {
+                let (keep_square, split_curve) =
+                    ctx.input(|input| (input.modifiers.shift, input.modifiers.alt));
+                let zoom = state.zoom;
                 if let Some(annotation) = state.find_annotation_mut(id) {
                     *annotation = original;
-                    annotation.resize_from_handle(handle, image_pos, keep_square);
+                    // Alt-dragging the bend handle gives each end its own control point.
+                    if split_curve && handle == Handle::ArrowControl {
+                        annotation.split_arrow_curve();
+                    }
+                    annotation.resize_from_handle(handle, image_pos, keep_square, zoom);
                 }
             }
         }
     }
 }
 
+fn resize_group(
+    state: &mut EditorState,
+    originals: &[Annotation],
+    handle: Handle,
+    image_pos: Point,
+    keep_square: bool,
+) {
+    let Some(old) = originals.iter().map(Annotation::bounds).reduce(Rect::union) else {
+        return;
+    };
+    let mut frame = RectData::from_rect(old);
+    frame.resize_from_handle(handle, image_pos, keep_square);
+    let new = frame.normalize().to_rect();
+    let ratio = |new: f32, old: f32| if old > 0.0 { new / old } else { 1.0 };
+    let mut scale = vec2(
+        ratio(new.width(), old.width()),
+        ratio(new.height(), old.height()),
+    );
+    let mut new_min = new.min;
+    // A rotated member would shear under an uneven scale, so such groups
+    // keep their proportions about the side or corner opposite the handle.
+    if originals.iter().any(|original| original.rotation != 0.0) {
+        let uniform = match handle {
+            Handle::Left | Handle::Right => scale.x,
+            Handle::Top | Handle::Bottom => scale.y,
+            _ => scale.x.max(scale.y),
+        };
+        let anchor = Pos2::new(
+            match handle {
+                Handle::Left | Handle::TopLeft | Handle::BottomLeft => old.max.x,
+                Handle::Right | Handle::TopRight | Handle::BottomRight => old.min.x,
+                _ => old.center().x,
+            },
+            match handle {
+                Handle::Top | Handle::TopLeft | Handle::TopRight => old.max.y,
+                Handle::Bottom | Handle::BottomLeft | Handle::BottomRight => old.min.y,
+                _ => old.center().y,
+            },
+        );
+        scale = Vec2::splat(uniform);
+        new_min = anchor + (old.min - anchor) * uniform;
+    }
+    for original in originals {
+        if let Some(annotation) = state.find_annotation_mut(original.id) {
+            *annotation = original.clone();
+            annotation.map_points(|point| {
+                let offset = point.to_pos2() - old.min;
+                Point::from_pos2(new_min + vec2(offset.x * scale.x, offset.y * scale.y))
+            });
+        }
+    }
+}
+
 fn finish_drag(ctx: &Context, state: &mut EditorState) {
     let Some(drag) = state.drag_state.take() else {
         return;
@@ -472,10 +1619,20 @@ fn finish_drag(ctx: &Context, state: &mut EditorState) {
     match drag.mode {
         DragMode::Draw => {
             let min_size = 5.0;
-            let dx = (drag.current.x - drag.start.x).abs();
-            let dy = (drag.current.y - drag.start.y).abs();
-            if (dx * dx + dy * dy).sqrt() < min_size {
-                return;
+            if matches!(state.active_tool, Tool::Freehand | Tool::Highlighter) {
+                let mut extent = Rect::NOTHING;
+                for point in &drag.points {
+                    extent.extend_with(point.to_pos2());
+                }
+                if extent.size().length() < min_size {
+                    return;
+                }
+            } else {
+                let dx = (drag.current.x - drag.start.x).abs();
+                let dy = (drag.current.y - drag.start.y).abs();
+                if (dx * dx + dy * dy).sqrt() < min_size {
+                    return;
+                }
             }
 
             match state.active_tool {
@@ -488,9 +1645,14 @@ fn finish_drag(ctx: &Context, state: &mut EditorState) {
                         kind: AnnotationKind::Arrow {
                             from: drag.start,
                             to: drag.current,
+                            head: state.active_arrow_head,
+                            curve: None,
                         },
                         color,
                         stroke_width: stroke,
+                        stroke_style: state.active_stroke_style,
+                        rotation: 0.0,
+                        group: None,
                     });
                     state.set_tool(Tool::Select);
                 }
@@ -502,6 +1664,7 @@ fn finish_drag(ctx: &Context, state: &mut EditorState) {
                         target: TextEditTarget::NewArrowText {
                             from: drag.start,
                             to: drag.current,
+                            head: state.active_arrow_head,
                             color: state.active_color,
                             stroke: state.active_stroke,
                         },
@@ -521,9 +1684,14 @@ fn finish_drag(ctx: &Context, state: &mut EditorState) {
                                 max: drag.current,
                             }
                             .normalize(),
+                            fill: state.active_fill(),
+                            corner_radius: 0.0,
                         },
                         color,
                         stroke_width: stroke,
+                        stroke_style: state.active_stroke_style,
+                        rotation: 0.0,
+                        group: None,
                     });
                     state.set_tool(Tool::Select);
                 }
@@ -539,15 +1707,163 @@ fn finish_drag(ctx: &Context, state: &mut EditorState) {
                                 max: drag.current,
                             }
                             .normalize(),
+                            fill: state.active_fill(),
+                        },
+                        color,
+                        stroke_width: stroke,
+                        stroke_style: state.active_stroke_style,
+                        rotation: 0.0,
+                        group: None,
+                    });
+                    state.set_tool(Tool::Select);
+                }
+                Tool::Freehand => {
+                    let id = state.next_annotation_id();
+                    let color = state.active_color;
+                    let stroke = state.active_stroke;
+                    state.add_annotation(Annotation {
+                        id,
+                        kind: AnnotationKind::Freehand {
+                            points: smooth_freehand(&drag.points),
+                        },
+                        color,
+                        stroke_width: stroke,
+                        stroke_style: state.active_stroke_style,
+                        rotation: 0.0,
+                        group: None,
+                    });
+                }
+                Tool::Callout => {
+                    state.text_edit = Some(TextEditState {
+                        buffer: String::new(),
+                        screen_pos: Pos2::ZERO,
+                        target: TextEditTarget::NewCallout {
+                            rect: RectData {
+                                min: drag.start,
+                                max: drag.current,
+                            }
+                            .normalize(),
+                            color: state.active_color,
+                            stroke: state.active_stroke,
+                        },
+                        text_size: state.active_text_size,
+                    });
+                    state.set_tool(Tool::Select);
+                }
+                Tool::Magnifier => {
+                    let Some(image_size) = state.image.as_ref().map(EditorImage::size_vec2) else {
+                        return;
+                    };
+                    let id = state.next_annotation_id();
+                    let source = RectData {
+                        min: drag.start,
+                        max: drag.current,
+                    }
+                    .normalize();
+                    let color = state.active_color;
+                    let stroke = state.active_stroke;
+                    state.add_annotation(Annotation {
+                        id,
+                        kind: AnnotationKind::Magnifier {
+                            source,
+                            target: default_loupe_target(source, image_size),
+                            shape: state.active_loupe_shape,
+                            connector: state.active_loupe_connector,
+                        },
+                        color,
+                        stroke_width: stroke,
+                        stroke_style: state.active_stroke_style,
+                        rotation: 0.0,
+                        group: None,
+                    });
+                    state.set_tool(Tool::Select);
+                }
+                Tool::Spotlight => {
+                    let id = state.next_annotation_id();
+                    let color = state.active_color;
+                    let stroke = state.active_stroke;
+                    state.add_annotation(Annotation {
+                        id,
+                        kind: AnnotationKind::Spotlight {
+                            rect: RectData {
+                                min: drag.start,
+                                max: drag.current,
+                            }
+                            .normalize(),
+                            shape: state.active_spotlight_shape,
+                            dim: state.active_spotlight_dim,
+                            desaturate: state.active_spotlight_desaturate,
+                        },
+                        color,
+                        stroke_width: stroke,
+                        stroke_style: state.active_stroke_style,
+                        rotation: 0.0,
+                        group: None,
+                    });
+                    state.set_tool(Tool::Select);
+                }
+                Tool::Redact => {
+                    let id = state.next_annotation_id();
+                    let color = state.active_color;
+                    let stroke = state.active_stroke;
+                    let mode = state.active_redact_mode;
+                    state.add_annotation(Annotation {
+                        id,
+                        kind: AnnotationKind::Redact {
+                            rect: RectData {
+                                min: drag.start,
+                                max: drag.current,
+                            }
+                            .normalize(),
+                            mode,
                         },
                         color,
                         stroke_width: stroke,
+                        stroke_style: state.active_stroke_style,
+                        rotation: 0.0,
+                        group: None,
                     });
                     state.set_tool(Tool::Select);
                 }
+                Tool::Stamp => {
+                    let rect = RectData {
+                        min: drag.start,
+                        max: drag.current,
+                    }
+                    .normalize();
+                    add_stamp(state, rect);
+                }
+                Tool::Highlighter => {
+                    let id = state.next_annotation_id();
+                    let color = state.active_highlight_color;
+                    let stroke = state.active_stroke;
+                    state.add_annotation(Annotation {
+                        id,
+                        kind: AnnotationKind::Highlighter {
+                            points: smooth_freehand(&drag.points),
+                        },
+                        color,
+                        stroke_width: stroke,
+                        stroke_style: state.active_stroke_style,
+                        rotation: 0.0,
+                        group: None,
+                    });
+                }
                 _ => {}
             }
         }
+        DragMode::Marquee => {
+            let band = Rect::from_two_pos(drag.start.to_pos2(), drag.current.to_pos2());
+            let caught: Vec<_> = state
+                .annotations
+                .iter()
+                .filter(|annotation| annotation.bounds().intersects(band))
+                .map(|annotation| annotation.id)
+                .collect();
+            for id in caught {
+                state.add_to_selection(id);
+            }
+        }
         DragMode::Move | DragMode::Resize => {
             let delta = drag.start.delta(drag.current);
             if delta.length_sq() > 0.01 {
@@ -569,7 +1885,14 @@ fn handle_click(
 ) {
     match state.active_tool {
         Tool::Select => {
-            state.selection = pick_annotation(state, image_pos);
+            let hit = pick_annotation(state, image_pos);
+            if ctx.input(|input| input.modifiers.shift) {
+                if let Some(id) = hit {
+                    state.toggle_selected(id);
+                }
+            } else {
+                state.select(hit);
+            }
         }
         Tool::Text => {
             state.text_edit = Some(TextEditState {
@@ -579,6 +1902,58 @@ fn handle_click(
                 text_size: state.active_text_size,
             });
         }
+        Tool::Step => {
+            let id = state.next_annotation_id();
+            let number = state.next_step_number();
+            let color = state.active_color;
+            let stroke = state.active_stroke;
+            state.add_annotation(Annotation {
+                id,
+                kind: AnnotationKind::Step {
+                    center: image_pos,
+                    number,
+                    size: state.active_text_size,
+                },
+                color,
+                stroke_width: stroke,
+                stroke_style: state.active_stroke_style,
+                rotation: 0.0,
+                group: None,
+            });
+        }
+        Tool::Stamp => {
+            let half = DEFAULT_STAMP_SIZE * 0.5;
+            let rect = RectData {
+                min: Point {
+                    x: image_pos.x - half,
+                    y: image_pos.y - half,
+                },
+                max: Point {
+                    x: image_pos.x + half,
+                    y: image_pos.y + half,
+                },
+            };
+            add_stamp(state, rect);
+        }
+        Tool::Polygon => {
+            let closes_on_first = state.polygon_draft.len() >= 3
+                && screen_pos.distance(image_to_screen(
+                    state.polygon_draft[0].to_pos2(),
+                    image_rect,
+                    state.zoom,
+                )) <= POLYGON_CLOSE_RADIUS;
+            if closes_on_first {
+                state.finish_polygon(true);
+                return;
+            }
+            // Ignore repeated clicks on the same spot.
+            let repeated = state.polygon_draft.last().is_some_and(|last| {
+                image_to_screen(last.to_pos2(), image_rect, state.zoom).distance(screen_pos) < 2.0
+            });
+            if !repeated {
+                state.polygon_draft.push(image_pos);
+            }
+        }
         _ => {}
     }
 
@@ -594,6 +1969,78 @@ fn handle_click(
     let _ = ctx;
 }
 
+fn add_stamp(state: &mut EditorState, rect: RectData) {
+    let id = state.next_annotation_id();
+    state.add_annotation(Annotation {
+        id,
+        kind: AnnotationKind::Stamp {
+            rect,
+            icon: state.active_stamp,
+        },
+        color: state.active_color,
+        stroke_width: state.active_stroke,
+        stroke_style: state.active_stroke_style,
+        rotation: 0.0,
+        group: None,
+    });
+}
+
+pub fn paint_stamp(
+    painter: &Painter,
+    icon: StampIcon,
+    rect: Rect,
+    turn: Turn,
+    color: Color32,
+    contrast: Color32,
+) {
+    let ink = |ink| match ink {
+        Ink::Color => color,
+        Ink::Contrast => contrast,
+    };
+    for part in stamp_parts(icon, rect) {
+        match part.turned(turn) {
+            StampPart::Stroke {
+                points,
+                width,
+                ink: part_ink,
+            } => {
+                let fill = ink(part_ink);
+                // Dots at every vertex give the line round caps and joins.
+                for point in &points {
+                    painter.circle_filled(*point, width * 0.5, fill);
+                }
+                painter.add(Shape::line(points, Stroke::new(width, fill)));
+            }
+            StampPart::Outline {
+                points,
+                width,
+                ink: part_ink,
+            } => {
+                let fill = ink(part_ink);
+                for point in &points {
+                    painter.circle_filled(*point, width * 0.5, fill);
+                }
+                painter.add(Shape::closed_line(points, Stroke::new(width, fill)));
+            }
+            StampPart::Fill {
+                points,
+                ink: part_ink,
+            } => {
+                let fill = ink(part_ink);
+                // A hairline in the same colour hides seams between pieces.
+                painter.add(Shape::convex_polygon(points, fill, Stroke::new(1.0, fill)));
+            }
+            StampPart::Circle {
+                center,
+                radius,
+                ink: part_ink,
+            } => {
+                painter.circle_filled(center, radius, ink(part_ink));
+            }
+        }
+    }
+}
+
 fn handle_double_click(state: &mut EditorState, image_pos: Point, screen_pos: Pos2) {
     let Some(id) = pick_annotation(state, image_pos) else {
         return;
@@ -605,14 +2052,15 @@ fn handle_double_click(state: &mut EditorState, image_pos: Point, screen_pos: Po
 
     match &annotation.kind {
         AnnotationKind::Text { content, .. }
-        | AnnotationKind::ArrowWithText { text: content, .. } => {
+        | AnnotationKind::ArrowWithText { text: content, .. }
+        | AnnotationKind::Callout { text: content, .. } => {
             let text_size = match &annotation.kind {
-                AnnotationKind::Text { size, .. } | AnnotationKind::ArrowWithText { size, .. } => {
-                    *size
-                }
+                AnnotationKind::Text { size, .. }
+                | AnnotationKind::ArrowWithText { size, .. }
+                | AnnotationKind::Callout { size, .. } => *size,
                 _ => TextSize::M,
             };
-            state.selection = Some(id);
+            state.selection = vec![id];
             state.text_edit = Some(TextEditState {
                 buffer: content.clone(),
                 screen_pos,
@@ -637,10 +2085,17 @@ fn draw_text_editor(ui: &mut Ui, state: &mut EditorState, image_rect: Rect) {
     let popup_id = Id::new("snapmark_text_edit");
 
     let mut screen_pos = edit.screen_pos;
-    if matches!(&target, TextEditTarget::NewArrowText { .. }) && screen_pos == Pos2::ZERO {
-        if let TextEditTarget::NewArrowText { from, to, .. } = &target {
-            let anchor = arrow_text_anchor(*from, *to);
-            screen_pos = image_to_screen(anchor.to_pos2(), image_rect, state.zoom);
+    if screen_pos == Pos2::ZERO {
+        match &target {
+            TextEditTarget::NewArrowText { from, to, .. } => {
+                let anchor = arrow_text_anchor(*from, *to);
+                screen_pos = image_to_screen(anchor.to_pos2(), image_rect, state.zoom);
+            }
+            TextEditTarget::NewCallout { rect, .. } => {
+                screen_pos = image_to_screen(rect.to_rect().left_bottom(), image_rect, state.zoom)
+                    + vec2(0.0, 8.0);
+            }
+            _ => {}
         }
     }
 
@@ -722,7 +2177,7 @@ fn draw_text_editor(ui: &mut Ui, state: &mut EditorState, image_rect: Rect) {
 
                     if pressed_enter {
                         match &target {
-                            TextEditTarget::NewText { .. } => {
+                            TextEditTarget::NewText { .. } | TextEditTarget::NewCallout { .. } => {
                                 if cmd_pressed {
                                     commit = true;
                                 }
@@ -739,6 +2194,7 @@ fn draw_text_editor(ui: &mut Ui, state: &mut EditorState, image_rect: Rect) {
                     } else if click_away {
                         match &target {
                             TextEditTarget::NewArrowText { .. }
+                            | TextEditTarget::NewCallout { .. }
                             | TextEditTarget::Existing { .. } => {
                                 commit = true;
                             }
@@ -793,26 +2249,44 @@ fn apply_text_edit(
                     pos,
                     content: new_content,
                     size: text_size,
+                    effects: state.active_text_effects,
+                    font: state.active_text_font.clone(),
+                    wrap_width: None,
+                    align: state.active_text_align,
+                    line_spacing: state.active_line_spacing,
                 },
                 color,
                 stroke_width: stroke,
+                stroke_style: state.active_stroke_style,
+                rotation: 0.0,
+                group: None,
             });
             state.set_tool(Tool::Select);
         }
         TextEditTarget::NewArrowText {
             from,
             to,
+            head,
             color,
             stroke,
         } => {
             let kind = if new_content.is_empty() {
-                AnnotationKind::Arrow { from, to }
+                AnnotationKind::Arrow {
+                    from,
+                    to,
+                    head,
+                    curve: None,
+                }
             } else {
                 AnnotationKind::ArrowWithText {
                     from,
                     to,
                     text: new_content,
                     size: text_size,
+                    head,
+                    curve: None,
+                    effects: state.active_text_effects,
+                    font: state.active_text_font.clone(),
                 }
             };
             let id = state.next_annotation_id();
@@ -821,6 +2295,35 @@ fn apply_text_edit(
                 kind,
                 color,
                 stroke_width: stroke,
+                stroke_style: state.active_stroke_style,
+                rotation: 0.0,
+                group: None,
+            });
+            state.set_tool(Tool::Select);
+        }
+        TextEditTarget::NewCallout {
+            rect,
+            color,
+            stroke,
+        } => {
+            if new_content.is_empty() {
+                state.text_edit = None;
+                return;
+            }
+            let id = state.next_annotation_id();
+            state.add_annotation(Annotation {
+                id,
+                kind: AnnotationKind::Callout {
+                    rect,
+                    tail: default_callout_tail(rect),
+                    text: new_content,
+                    size: text_size,
+                },
+                color,
+                stroke_width: stroke,
+                stroke_style: state.active_stroke_style,
+                rotation: 0.0,
+                group: None,
             });
             state.set_tool(Tool::Select);
         }
@@ -837,6 +2340,11 @@ fn apply_text_edit(
                         text: content,
                         size,
                         ..
+                    }
+                    | AnnotationKind::Callout {
+                        text: content,
+                        size,
+                        ..
                     } => {
                         *content = new_content.clone();
                         *size = text_size;
@@ -860,13 +2368,21 @@ fn detect_handle_hit(
     image_pos: Point,
     image_rect: Rect,
 ) -> Option<(Handle, Point)> {
-    let tolerance = 8.0 / state.zoom.max(0.25);
     let annotation = state
         .annotations
         .iter()
         .find(|item| item.id == annotation_id)?;
+    hit_handle(state, annotation.handles(state.zoom), image_pos, image_rect)
+}
 
-    for (handle, point) in annotation.handles() {
+fn hit_handle(
+    state: &EditorState,
+    handles: Vec<(Handle, Point)>,
+    image_pos: Point,
+    image_rect: Rect,
+) -> Option<(Handle, Point)> {
+    let tolerance = 8.0 / state.zoom.max(0.25);
+    for (handle, point) in handles {
         let screen = image_to_screen(point.to_pos2(), image_rect, state.zoom);
         let hit = Rect::from_center_size(screen, vec2(12.0, 12.0));
         if hit.contains(image_to_screen(image_pos.to_pos2(), image_rect, state.zoom)) {
@@ -890,6 +2406,42 @@ fn pick_annotation(state: &EditorState, image_pos: Point) -> Option<u64> {
         .map(|annotation| annotation.id)
 }
 
+fn fill_color32(fill: Option<[u8; 4]>, preview: bool) -> Option<Color32> {
+    let [r, g, b, a] = fill.filter(|fill| fill[3] > 0)?;
+    let color = Color32::from_rgba_unmultiplied(r, g, b, a);
+    Some(if preview {
+        color.linear_multiply(0.7)
+    } else {
+        color
+    })
+}
+
+fn loupe_outline(rect: Rect, shape: LoupeShape) -> Vec<Pos2> {
+    match shape {
+        LoupeShape::Circle => ellipse_polyline(rect, 96),
+        LoupeShape::RoundedRect => {
+            rounded_rect_outline(rect, rect.width().min(rect.height()) * LOUPE_ROUNDING)
+        }
+    }
+}
+
+fn redaction_screen_rect(region: PixelRegion, image_rect: Rect, zoom: f32) -> Rect {
+    let min = image_to_screen(
+        Pos2::new(region.x as f32, region.y as f32),
+        image_rect,
+        zoom,
+    );
+    let max = image_to_screen(
+        Pos2::new(
+            (region.x + region.width) as f32,
+            (region.y + region.height) as f32,
+        ),
+        image_rect,
+        zoom,
+    );
+    Rect::from_min_max(min, max)
+}
+
 fn ellipse_polyline(rect: Rect, segments: usize) -> Vec<Pos2> {
     let mut points = Vec::with_capacity(segments);
     let center = rect.center();
@@ -904,15 +2456,137 @@ fn ellipse_polyline(rect: Rect, segments: usize) -> Vec<Pos2> {
     points
 }
 
-fn arrow_text_anchor(from: Point, to: Point) -> Point {
-    let dir = from.delta(to);
-    let len = dir.length().max(1.0);
-    let unit = dir / len;
-    let perp_a = vec2(-unit.y, unit.x);
-    let perp_b = vec2(unit.y, -unit.x);
-    let up_perp = if perp_a.y < perp_b.y { perp_a } else { perp_b };
-    let offset = up_perp * 12.0 + vec2(6.0, -2.0);
-    Point::new(from.x + offset.x, from.y + offset.y)
+#[allow(clippy::too_many_arguments)]
+fn draw_annotation_text(
+    painter: &Painter,
+    image: &EditorImage,
+    annotation: &Annotation,
+    size: TextSize,
+    effects: &TextEffects,
+    image_rect: Rect,
+    scale: f32,
+    color: Color32,
+    preview: bool,
+) {
+    let Some(layout) = annotation.text_layout() else {
+        return;
+    };
+    let Some(origin) = annotation.text_origin(&layout) else {
+        return;
+    };
+    let pos = image_to_screen(origin.to_pos2(), image_rect, scale);
+    let turn = screen_turn(annotation, image_rect, scale);
+    let text_font = annotation.text_font();
+    let effect_color = |rgba: [u8; 4]| {
+        let color = Color32::from_rgba_unmultiplied(rgba[0], rgba[1], rgba[2], rgba[3]);
+        if preview {
+            color.linear_multiply(0.7)
+        } else {
+            color
+        }
+    };
+    // Each glyph is placed where the shared layout puts it, so the canvas
+    // matches the export even where egui would round advances differently.
+    let font = FontId::new(size.points() * scale, egui_family(&text_font));
+    let chain = font_chain(&text_font);
+    let glyphs: Vec<_> = layout
+        .lines
+        .iter()
+        .flat_map(|line| {
+            let (glyphs, _) = line_glyphs(chain, size.points(), &line.text);
+            glyphs
+                .into_iter()
+                .filter(|placed| !placed.ch.is_whitespace())
+                .map(|placed| {
+                    let galley = painter.layout_no_wrap(placed.ch.to_string(), font.clone(), color);
+                    let offset = vec2(line.x + placed.glyph.position.x, line.y);
+                    (offset * scale, galley)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    if effects.background {
+        let padding = TextEffects::plate_padding(size) * scale;
+        let plate =
+            Rect::from_min_size(pos, vec2(layout.width, layout.height) * scale).expand(padding);
+        painter.add(Shape::convex_polygon(
+            rounded_rect_outline(plate, padding)
+                .into_iter()
+                .map(|point| turn.apply(point))
+                .collect(),
+            effect_color(effects.background_color),
+            Stroke::NONE,
+        ));
+    }
+    // Glyphs turn about their own corner, which is itself turned into place.
+    let paint_text = |offset: Vec2, tint: Color32| {
+        for (glyph_offset, galley) in &glyphs {
+            painter.add(
+                TextShape::new(
+                    turn.apply(pos + offset + *glyph_offset),
+                    galley.clone(),
+                    tint,
+                )
+                .with_override_text_color(tint)
+                .with_angle(turn.angle),
+            );
+        }
+    };
+    if effects.shadow {
+        let offset = Vec2::splat(TextEffects::shadow_offset(size) * scale);
+        paint_text(offset, effect_color(TEXT_SHADOW_COLOR));
+    }
+    if let Some(halo) = image.text_halos.get(&annotation.id) {
+        paint_text_halo(
+            painter,
+            halo,
+            pos,
+            scale,
+            turn,
+            effect_color(effects.outline_color),
+        );
+    }
+    paint_text(Vec2::ZERO, color);
+}
+
+fn paint_text_halo(
+    painter: &Painter,
+    halo: &TextHalo,
+    pos: Pos2,
+    zoom: f32,
+    turn: Turn,
+    tint: Color32,
+) {
+    // The mask may trail the zoom by a frame; scaling it keeps it in place.
+    let points_per_pixel = zoom / halo.scale;
+    let rect = Rect::from_min_size(
+        pos + vec2(halo.left as f32, halo.top as f32) * points_per_pixel,
+        halo.texture.size_vec2() * points_per_pixel,
+    );
+    let mut mesh = Mesh::with_texture(halo.texture.id());
+    for (corner, uv) in [
+        (rect.left_top(), Pos2::new(0.0, 0.0)),
+        (rect.right_top(), Pos2::new(1.0, 0.0)),
+        (rect.right_bottom(), Pos2::new(1.0, 1.0)),
+        (rect.left_bottom(), Pos2::new(0.0, 1.0)),
+    ] {
+        mesh.vertices.push(Vertex {
+            pos: turn.apply(corner),
+            uv,
+            color: tint,
+        });
+    }
+    mesh.add_triangle(0, 1, 2);
+    mesh.add_triangle(0, 2, 3);
+    painter.add(Shape::mesh(mesh));
+}
+
+fn screen_turn(annotation: &Annotation, image_rect: Rect, zoom: f32) -> Turn {
+    let turn = annotation.turn();
+    Turn {
+        center: image_to_screen(turn.center, image_rect, zoom),
+        ..turn
+    }
 }
 
 fn image_to_screen(pos: Pos2, image_rect: Rect, zoom: f32) -> Pos2 {
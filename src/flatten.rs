@@ -1,10 +1,21 @@
-use ab_glyph::FontArc;
 use anyhow::{anyhow, Context, Result};
-use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
-use imageproc::drawing::draw_text_mut;
-use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+use egui::Vec2;
+use image::{DynamicImage, GrayImage, ImageFormat, RgbaImage};
+use tiny_skia::{
+    BlendMode, FillRule, FilterQuality, LineCap, LineJoin, Mask, Paint, PathBuilder, Pattern,
+    Pixmap, Rect, SpreadMode, Stroke, StrokeDash, Transform,
+};
 
-use crate::annotation::{Annotation, AnnotationKind, Point};
+use crate::annotation::{
+    arrow_end_directions, callout_tail_base, magnifier_connector, step_radius, Annotation,
+    AnnotationKind, ArrowCurve, ArrowHead, LoupeShape, Point, RectData, StampIcon, StrokeStyle,
+    TextEffects, TextSize, Turn, CALLOUT_ROUNDING, LOUPE_ROUNDING, TEXT_SHADOW_COLOR,
+};
+use crate::fonts::{font_chain, FontChain};
+use crate::redaction::{self, PixelRegion};
+use crate::spotlight::Spotlight;
+use crate::stamp::{stamp_parts, Ink, StampPart};
+use crate::text_layout::{TextLayout, TextStamp};
 
 pub fn flatten(
     image: &DynamicImage,
@@ -14,17 +25,54 @@ pub fn flatten(
     let mut pixmap = Pixmap::new(image.width(), image.height())
         .ok_or_else(|| anyhow!("cannot allocate pixmap"))?;
 
-    copy_image_to_pixmap(image, &mut pixmap)?;
+    // Redactions rewrite the source pixels before anything is drawn on top,
+    // so the hidden content never reaches the exported image.
+    let mut base = image.to_rgba8();
+    for annotation in annotations {
+        if let AnnotationKind::Redact { rect, mode } = &annotation.kind {
+            if let Some(region) = PixelRegion::from_rect(*rect, scale, base.width(), base.height())
+            {
+                redaction::apply_redaction(&mut base, region, *mode, annotation.color);
+            }
+        }
+    }
 
+    // Loupes enlarge the redacted photo as it was before any dimming.
+    let magnifier_source = if annotations
+        .iter()
+        .any(|annotation| matches!(annotation.kind, AnnotationKind::Magnifier { .. }))
+    {
+        let mut source = Pixmap::new(base.width(), base.height())
+            .ok_or_else(|| anyhow!("cannot allocate magnifier source"))?;
+        copy_image_to_pixmap(&base, &mut source)?;
+        Some(source)
+    } else {
+        None
+    };
+
+    // Spotlights dim the (already redacted) photo only; annotations drawn
+    // afterwards stay at full strength.
+    if let Some(spotlight) = Spotlight::from_annotations(annotations) {
+        spotlight.apply(&mut base, scale)?;
+    }
+
+    copy_image_to_pixmap(&base, &mut pixmap)?;
+
+    // Each annotation's text goes on right after its shape, so later
+    // annotations cover both, in the same order as the canvas.
     for annotation in annotations {
-        draw_annotation_shape(&mut pixmap, annotation, scale)?;
+        match (&annotation.kind, magnifier_source.as_ref()) {
+            (AnnotationKind::Magnifier { .. }, Some(source)) => {
+                draw_magnifier(&mut pixmap, source, annotation, scale)?
+            }
+            _ => draw_annotation_shape(&mut pixmap, annotation, scale)?,
+        }
+        draw_annotation_text(&mut pixmap, annotation, scale)?;
     }
 
-    let mut output = RgbaImage::from_raw(image.width(), image.height(), pixmap.data().to_vec())
+    let output = RgbaImage::from_raw(image.width(), image.height(), pixmap.data().to_vec())
         .ok_or_else(|| anyhow!("cannot construct output image"))?;
 
-    draw_text_annotations(&mut output, annotations, scale);
-
     Ok(DynamicImage::ImageRgba8(output))
 }
 
@@ -36,8 +84,7 @@ pub fn encode_png(image: &DynamicImage) -> Result<Vec<u8>> {
     Ok(buffer.into_inner())
 }
 
-fn copy_image_to_pixmap(image: &DynamicImage, pixmap: &mut Pixmap) -> Result<()> {
-    let rgba = image.to_rgba8();
+fn copy_image_to_pixmap(rgba: &RgbaImage, pixmap: &mut Pixmap) -> Result<()> {
     let data = pixmap.data_mut();
     if data.len() != rgba.len() {
         return Err(anyhow!("source image and pixmap size mismatch"));
@@ -60,13 +107,62 @@ fn draw_annotation_shape(pixmap: &mut Pixmap, annotation: &Annotation, scale: f3
         width: annotation.stroke_width.px() * scale,
         ..Default::default()
     };
+    // Arrow heads stay solid; only shafts and outlines take the dash pattern.
+    let outline = styled_stroke(&stroke, annotation.stroke_style);
+    let transform = annotation_transform(annotation, scale);
 
     match &annotation.kind {
-        AnnotationKind::Arrow { from, to } | AnnotationKind::ArrowWithText { from, to, .. } => {
-            stroke_line(pixmap, *from, *to, &paint, &stroke, scale)?;
-            fill_arrow_head(pixmap, *from, *to, &paint, scale)?;
+        AnnotationKind::Arrow {
+            from,
+            to,
+            head,
+            curve,
+        }
+        | AnnotationKind::ArrowWithText {
+            from,
+            to,
+            head,
+            curve,
+            ..
+        } => {
+            stroke_arrow_body(pixmap, *from, *to, *curve, &paint, &outline, scale)?;
+            // Aim each head from a point just behind its tip on the curve tangent.
+            let (start_dir, end_dir) = arrow_end_directions(*from, *to, *curve);
+            let (start_dir, end_dir) = (start_dir.normalized(), end_dir.normalized());
+            let behind_to = Point::new(to.x + end_dir.x, to.y + end_dir.y);
+            let behind_from = Point::new(from.x + start_dir.x, from.y + start_dir.y);
+            match head {
+                ArrowHead::None => {}
+                ArrowHead::Open | ArrowHead::Filled | ArrowHead::Dot => {
+                    draw_arrow_end(pixmap, behind_to, *to, *head, &paint, &stroke, scale)?;
+                }
+                ArrowHead::Double => {
+                    draw_arrow_end(
+                        pixmap,
+                        behind_to,
+                        *to,
+                        ArrowHead::Filled,
+                        &paint,
+                        &stroke,
+                        scale,
+                    )?;
+                    draw_arrow_end(
+                        pixmap,
+                        behind_from,
+                        *from,
+                        ArrowHead::Filled,
+                        &paint,
+                        &stroke,
+                        scale,
+                    )?;
+                }
+            }
         }
-        AnnotationKind::Rectangle { rect } => {
+        AnnotationKind::Rectangle {
+            rect,
+            fill,
+            corner_radius,
+        } => {
             let rect = rect.normalize();
             let tiny_rect = Rect::from_ltrb(
                 rect.min.x * scale,
@@ -75,52 +171,458 @@ fn draw_annotation_shape(pixmap: &mut Pixmap, annotation: &Annotation, scale: f3
                 rect.max.y * scale,
             )
             .ok_or_else(|| anyhow!("invalid rectangle"))?;
-            let path = PathBuilder::from_rect(tiny_rect);
-            pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+            let path = if *corner_radius > 0.0 {
+                let mut pb = PathBuilder::new();
+                push_rounded_rect(
+                    &mut pb,
+                    tiny_rect.left(),
+                    tiny_rect.top(),
+                    tiny_rect.right(),
+                    tiny_rect.bottom(),
+                    corner_radius * scale,
+                );
+                pb.finish()
+                    .ok_or_else(|| anyhow!("invalid rounded rectangle"))?
+            } else {
+                PathBuilder::from_rect(tiny_rect)
+            };
+            fill_shape(pixmap, &path, *fill, transform);
+            pixmap.stroke_path(&path, &paint, &outline, transform, None);
         }
-        AnnotationKind::Ellipse { rect } => {
+        AnnotationKind::Ellipse { rect, fill } => {
+            // Built in pixel space so the stroke width and dashes aren't
+            // stretched along with the ellipse.
             let rect = rect.normalize().to_rect();
             let center = rect.center();
             let rx = (rect.width() * 0.5 * scale).max(1.0);
             let ry = (rect.height() * 0.5 * scale).max(1.0);
+            let bounds = Rect::from_ltrb(
+                center.x * scale - rx,
+                center.y * scale - ry,
+                center.x * scale + rx,
+                center.y * scale + ry,
+            )
+            .ok_or_else(|| anyhow!("invalid ellipse"))?;
+            let path = PathBuilder::from_oval(bounds)
+                .ok_or_else(|| anyhow!("cannot build ellipse path"))?;
+            fill_shape(pixmap, &path, *fill, transform);
+            pixmap.stroke_path(&path, &paint, &outline, transform, None);
+        }
+        AnnotationKind::Freehand { points } => {
+            let stroke = Stroke {
+                line_cap: LineCap::Round,
+                line_join: LineJoin::Round,
+                ..outline
+            };
+            stroke_polyline(pixmap, points, &paint, &stroke, scale)?;
+        }
+        AnnotationKind::Highlighter { points } => {
+            // Multiply darkens only the light background, so text under the
+            // marker keeps its contrast in the exported image.
+            let paint = Paint {
+                blend_mode: BlendMode::Multiply,
+                ..paint
+            };
+            let stroke = Stroke {
+                width: annotation.line_width() * scale,
+                line_join: LineJoin::Round,
+                ..stroke
+            };
+            stroke_polyline(pixmap, points, &paint, &stroke, scale)?;
+        }
+        AnnotationKind::Polygon {
+            points,
+            closed,
+            filled,
+        } => {
+            let closed = *closed && points.len() >= 3;
             let mut pb = PathBuilder::new();
-            pb.push_circle(0.0, 0.0, 1.0);
+            for (index, point) in points.iter().enumerate() {
+                if index == 0 {
+                    pb.move_to(point.x * scale, point.y * scale);
+                } else {
+                    pb.line_to(point.x * scale, point.y * scale);
+                }
+            }
+            if closed {
+                pb.close();
+            }
             let path = pb
                 .finish()
-                .ok_or_else(|| anyhow!("cannot build ellipse path"))?;
-            let transform =
-                Transform::from_scale(rx, ry).post_translate(center.x * scale, center.y * scale);
-            pixmap.stroke_path(&path, &paint, &stroke, transform, None);
+                .ok_or_else(|| anyhow!("cannot build polygon path"))?;
+            if closed && *filled {
+                let [r, g, b, a] = annotation.polygon_fill_color();
+                let mut fill = paint.clone();
+                fill.set_color_rgba8(r, g, b, a);
+                pixmap.fill_path(&path, &fill, FillRule::EvenOdd, transform, None);
+            }
+            pixmap.stroke_path(&path, &paint, &outline, transform, None);
         }
         AnnotationKind::Text { .. } => {
-            // Text is rendered in a separate pass via imageproc.
+            // Text is rendered in a separate pass with ab_glyph.
+        }
+        AnnotationKind::Magnifier { .. } => {
+            // Loupes need the source pixels and are drawn by `draw_magnifier`.
+        }
+        AnnotationKind::Redact { .. } | AnnotationKind::Spotlight { .. } => {
+            // Redactions and spotlights are applied to the source pixels before shapes.
+        }
+        AnnotationKind::Callout { rect, tail, .. } => {
+            let r = rect.normalize();
+            let mut pb = PathBuilder::new();
+            push_rounded_rect(
+                &mut pb,
+                r.min.x * scale,
+                r.min.y * scale,
+                r.max.x * scale,
+                r.max.y * scale,
+                CALLOUT_ROUNDING * scale,
+            );
+            let (base_a, base_b) = callout_tail_base(*rect, *tail);
+            pb.move_to(base_a.x * scale, base_a.y * scale);
+            pb.line_to(tail.x * scale, tail.y * scale);
+            pb.line_to(base_b.x * scale, base_b.y * scale);
+            pb.close();
+            let path = pb
+                .finish()
+                .ok_or_else(|| anyhow!("cannot build callout path"))?;
+            pixmap.fill_path(&path, &paint, FillRule::Winding, transform, None);
+        }
+        AnnotationKind::Stamp { rect, icon } => {
+            let contrast = annotation.contrast_text_color();
+            draw_stamp(pixmap, *rect, *icon, &paint, contrast, scale, transform)?;
+        }
+        AnnotationKind::Step { center, size, .. } => {
+            let mut pb = PathBuilder::new();
+            pb.push_circle(
+                center.x * scale,
+                center.y * scale,
+                step_radius(*size) * scale,
+            );
+            let path = pb
+                .finish()
+                .ok_or_else(|| anyhow!("cannot build step badge path"))?;
+            pixmap.fill_path(&path, &paint, FillRule::Winding, transform, None);
         }
     }
 
     Ok(())
 }
 
-fn stroke_line(
+fn draw_stamp(
+    pixmap: &mut Pixmap,
+    rect: RectData,
+    icon: StampIcon,
+    paint: &Paint,
+    contrast: [u8; 4],
+    scale: f32,
+    transform: Transform,
+) -> Result<()> {
+    let r = rect.normalize().to_rect();
+    let target = egui::Rect::from_min_max(
+        (r.min.to_vec2() * scale).to_pos2(),
+        (r.max.to_vec2() * scale).to_pos2(),
+    );
+    let mut contrast_paint = paint.clone();
+    contrast_paint.set_color_rgba8(contrast[0], contrast[1], contrast[2], contrast[3]);
+    let ink = |ink| match ink {
+        Ink::Color => paint,
+        Ink::Contrast => &contrast_paint,
+    };
+    let polyline = |points: &[egui::Pos2], close: bool| {
+        let mut pb = PathBuilder::new();
+        for (index, point) in points.iter().enumerate() {
+            if index == 0 {
+                pb.move_to(point.x, point.y);
+            } else {
+                pb.line_to(point.x, point.y);
+            }
+        }
+        if close {
+            pb.close();
+        }
+        pb.finish()
+            .ok_or_else(|| anyhow!("cannot build stamp path"))
+    };
+
+    for part in stamp_parts(icon, target) {
+        let close = matches!(part, StampPart::Outline { .. });
+        match part {
+            StampPart::Stroke {
+                points,
+                width,
+                ink: part_ink,
+            }
+            | StampPart::Outline {
+                points,
+                width,
+                ink: part_ink,
+            } => {
+                let stroke = Stroke {
+                    width,
+                    line_cap: LineCap::Round,
+                    line_join: LineJoin::Round,
+                    ..Default::default()
+                };
+                let path = polyline(&points, close)?;
+                pixmap.stroke_path(&path, ink(part_ink), &stroke, transform, None);
+            }
+            StampPart::Fill {
+                points,
+                ink: part_ink,
+            } => {
+                let path = polyline(&points, true)?;
+                pixmap.fill_path(&path, ink(part_ink), FillRule::Winding, transform, None);
+            }
+            StampPart::Circle {
+                center,
+                radius,
+                ink: part_ink,
+            } => {
+                let mut pb = PathBuilder::new();
+                pb.push_circle(center.x, center.y, radius);
+                let path = pb
+                    .finish()
+                    .ok_or_else(|| anyhow!("cannot build stamp path"))?;
+                pixmap.fill_path(&path, ink(part_ink), FillRule::Winding, transform, None);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The annotation's rotation about its pivot, in output pixels.
+fn annotation_transform(annotation: &Annotation, scale: f32) -> Transform {
+    let turn = annotation.turn();
+    Transform::from_rotate_at(
+        turn.angle.to_degrees(),
+        turn.center.x * scale,
+        turn.center.y * scale,
+    )
+}
+
+/// `stroke` with the dash pattern of `style` applied.
+fn styled_stroke(stroke: &Stroke, style: StrokeStyle) -> Stroke {
+    Stroke {
+        dash: style
+            .dash_pattern(stroke.width)
+            .and_then(|[on, off]| StrokeDash::new(vec![on, off], 0.0)),
+        line_cap: if style == StrokeStyle::Dotted {
+            LineCap::Round
+        } else {
+            stroke.line_cap
+        },
+        ..stroke.clone()
+    }
+}
+
+fn stroke_arrow_body(
     pixmap: &mut Pixmap,
     from: Point,
     to: Point,
+    curve: Option<ArrowCurve>,
     paint: &Paint,
     stroke: &Stroke,
     scale: f32,
 ) -> Result<()> {
     let mut pb = PathBuilder::new();
     pb.move_to(from.x * scale, from.y * scale);
-    pb.line_to(to.x * scale, to.y * scale);
-    let path = pb.finish().ok_or_else(|| anyhow!("cannot build line"))?;
+    match curve {
+        None => pb.line_to(to.x * scale, to.y * scale),
+        Some(ArrowCurve::Quadratic { control }) => pb.quad_to(
+            control.x * scale,
+            control.y * scale,
+            to.x * scale,
+            to.y * scale,
+        ),
+        Some(ArrowCurve::Cubic {
+            control_a,
+            control_b,
+        }) => pb.cubic_to(
+            control_a.x * scale,
+            control_a.y * scale,
+            control_b.x * scale,
+            control_b.y * scale,
+            to.x * scale,
+            to.y * scale,
+        ),
+    }
+    let path = pb.finish().ok_or_else(|| anyhow!("cannot build arrow"))?;
+    pixmap.stroke_path(&path, paint, stroke, Transform::identity(), None);
+    Ok(())
+}
+
+/// Paints the interior of a rectangle or ellipse, if it has a fill.
+fn fill_shape(
+    pixmap: &mut Pixmap,
+    path: &tiny_skia::Path,
+    fill: Option<[u8; 4]>,
+    transform: Transform,
+) {
+    let Some([r, g, b, a]) = fill.filter(|fill| fill[3] > 0) else {
+        return;
+    };
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, a);
+    paint.anti_alias = true;
+    pixmap.fill_path(path, &paint, FillRule::Winding, transform, None);
+}
+
+/// Resamples the loupe's source region from `source` into its frame, then
+/// outlines both frames and draws the connector.
+fn draw_magnifier(
+    pixmap: &mut Pixmap,
+    source: &Pixmap,
+    annotation: &Annotation,
+    scale: f32,
+) -> Result<()> {
+    let AnnotationKind::Magnifier {
+        source: source_rect,
+        target,
+        shape,
+        connector,
+    } = &annotation.kind
+    else {
+        return Ok(());
+    };
+    let from = source_rect.normalize();
+    let to = target.normalize();
+    let source_w = from.max.x - from.min.x;
+    let source_h = from.max.y - from.min.y;
+    if source_w < 1.0 || source_h < 1.0 {
+        return Ok(());
+    }
+    let sx = (to.max.x - to.min.x) / source_w;
+    let sy = (to.max.y - to.min.y) / source_h;
+
+    let Some(frame) = loupe_path(to, *shape, scale) else {
+        return Ok(());
+    };
+    let zoomed = Paint {
+        shader: Pattern::new(
+            source.as_ref(),
+            SpreadMode::Pad,
+            FilterQuality::Bicubic,
+            1.0,
+            Transform::from_row(
+                sx,
+                0.0,
+                0.0,
+                sy,
+                (to.min.x - from.min.x * sx) * scale,
+                (to.min.y - from.min.y * sy) * scale,
+            ),
+        ),
+        anti_alias: true,
+        ..Default::default()
+    };
+    pixmap.fill_path(
+        &frame,
+        &zoomed,
+        FillRule::Winding,
+        Transform::identity(),
+        None,
+    );
+
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(
+        annotation.color[0],
+        annotation.color[1],
+        annotation.color[2],
+        annotation.color[3],
+    );
+    paint.anti_alias = true;
+    let stroke = Stroke {
+        width: annotation.stroke_width.px() * scale,
+        ..Default::default()
+    };
+    pixmap.stroke_path(&frame, &paint, &stroke, Transform::identity(), None);
+    if let Some(source_frame) = loupe_path(from, *shape, scale) {
+        pixmap.stroke_path(&source_frame, &paint, &stroke, Transform::identity(), None);
+    }
+    if *connector {
+        if let Some((start, end)) = magnifier_connector(*source_rect, *target, *shape) {
+            stroke_polyline(pixmap, &[start, end], &paint, &stroke, scale)?;
+        }
+    }
+    Ok(())
+}
+
+fn loupe_path(rect: RectData, shape: LoupeShape, scale: f32) -> Option<tiny_skia::Path> {
+    let (left, top) = (rect.min.x * scale, rect.min.y * scale);
+    let (right, bottom) = (rect.max.x * scale, rect.max.y * scale);
+    match shape {
+        LoupeShape::Circle => PathBuilder::from_oval(Rect::from_ltrb(left, top, right, bottom)?),
+        LoupeShape::RoundedRect => {
+            let mut pb = PathBuilder::new();
+            let radius = (right - left).min(bottom - top) * LOUPE_ROUNDING;
+            push_rounded_rect(&mut pb, left, top, right, bottom, radius);
+            pb.finish()
+        }
+    }
+}
+
+/// Appends a closed rounded rectangle; the radius is clamped to half the
+/// shorter side.
+fn push_rounded_rect(
+    pb: &mut PathBuilder,
+    left: f32,
+    top: f32,
+    right: f32,
+    bottom: f32,
+    radius: f32,
+) {
+    let r = radius
+        .min((right - left) * 0.5)
+        .min((bottom - top) * 0.5)
+        .max(0.0);
+    pb.move_to(left + r, top);
+    pb.line_to(right - r, top);
+    pb.quad_to(right, top, right, top + r);
+    pb.line_to(right, bottom - r);
+    pb.quad_to(right, bottom, right - r, bottom);
+    pb.line_to(left + r, bottom);
+    pb.quad_to(left, bottom, left, bottom - r);
+    pb.line_to(left, top + r);
+    pb.quad_to(left, top, left + r, top);
+    pb.close();
+}
+
+fn stroke_polyline(
+    pixmap: &mut Pixmap,
+    points: &[Point],
+    paint: &Paint,
+    stroke: &Stroke,
+    scale: f32,
+) -> Result<()> {
+    let Some((first, rest)) = points.split_first() else {
+        return Ok(());
+    };
+
+    let mut pb = PathBuilder::new();
+    pb.move_to(first.x * scale, first.y * scale);
+    if rest.is_empty() {
+        // A single click still leaves a dot thanks to the round cap.
+        pb.line_to(first.x * scale + 0.01, first.y * scale);
+    }
+    for point in rest {
+        pb.line_to(point.x * scale, point.y * scale);
+    }
+    let path = pb
+        .finish()
+        .ok_or_else(|| anyhow!("cannot build polyline"))?;
     pixmap.stroke_path(&path, paint, stroke, Transform::identity(), None);
     Ok(())
 }
 
-fn fill_arrow_head(
+/// Draws a single cap at `to`, oriented along the segment coming from `from`.
+fn draw_arrow_end(
     pixmap: &mut Pixmap,
     from: Point,
     to: Point,
+    head: ArrowHead,
     paint: &Paint,
+    stroke: &Stroke,
     scale: f32,
 ) -> Result<()> {
     let dx = to.x - from.x;
@@ -141,103 +643,371 @@ fn fill_arrow_head(
     let right_y = base_y - ux * head_w;
 
     let mut pb = PathBuilder::new();
-    pb.move_to(tip_x, tip_y);
-    pb.line_to(left_x, left_y);
-    pb.line_to(right_x, right_y);
-    pb.close();
+    match head {
+        ArrowHead::Filled | ArrowHead::Double => {
+            pb.move_to(tip_x, tip_y);
+            pb.line_to(left_x, left_y);
+            pb.line_to(right_x, right_y);
+            pb.close();
+        }
+        ArrowHead::Open => {
+            pb.move_to(left_x, left_y);
+            pb.line_to(tip_x, tip_y);
+            pb.line_to(right_x, right_y);
+        }
+        ArrowHead::Dot => {
+            let radius = (stroke.width * 1.5).max(4.0 * scale);
+            pb.push_circle(tip_x, tip_y, radius);
+        }
+        ArrowHead::None => return Ok(()),
+    }
     let path = pb
         .finish()
         .ok_or_else(|| anyhow!("cannot build arrow head path"))?;
-    pixmap.fill_path(&path, paint, FillRule::Winding, Transform::identity(), None);
+    if head == ArrowHead::Open {
+        let stroke = Stroke {
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+            ..stroke.clone()
+        };
+        pixmap.stroke_path(&path, paint, &stroke, Transform::identity(), None);
+    } else {
+        pixmap.fill_path(&path, paint, FillRule::Winding, Transform::identity(), None);
+    }
     Ok(())
 }
 
-fn draw_text_annotations(image: &mut RgbaImage, annotations: &[Annotation], scale: f32) {
-    let Some(font) = load_system_font() else {
-        return;
+fn draw_annotation_text(pixmap: &mut Pixmap, annotation: &Annotation, scale: f32) -> Result<()> {
+    let (size, effects, color) = match &annotation.kind {
+        AnnotationKind::Text { size, effects, .. }
+        | AnnotationKind::ArrowWithText { size, effects, .. } => {
+            (*size, *effects, annotation.color)
+        }
+        AnnotationKind::Callout { size, .. } | AnnotationKind::Step { size, .. } => {
+            // Drawn after the badge or box fill, in its contrasting colour.
+            (
+                *size,
+                TextEffects::default(),
+                annotation.contrast_text_color(),
+            )
+        }
+        _ => return Ok(()),
+    };
+    let Some(layout) = annotation.text_layout() else {
+        return Ok(());
     };
+    let Some(origin) = annotation.text_origin(&layout) else {
+        return Ok(());
+    };
+    let font = font_chain(&annotation.text_font());
+    for line in &layout.lines {
+        font.ensure_covers(&line.text)?;
+    }
+    draw_styled_text(
+        pixmap,
+        font,
+        origin,
+        &layout,
+        size,
+        &effects,
+        color,
+        scale,
+        annotation.turn(),
+    );
+    Ok(())
+}
 
-    for annotation in annotations {
-        match &annotation.kind {
-            AnnotationKind::Text { pos, content, size } => {
-                draw_text_mut(
-                    image,
-                    Rgba(annotation.color),
-                    (pos.x * scale) as i32,
-                    (pos.y * scale) as i32,
-                    size.points() * scale,
-                    &font,
-                    content,
-                );
-            }
-            AnnotationKind::ArrowWithText {
-                from,
-                to,
-                text,
-                size,
-            } => {
-                let anchor = arrow_text_anchor(*from, *to);
-                draw_text_mut(
-                    image,
-                    Rgba(annotation.color),
-                    (anchor.x * scale) as i32,
-                    (anchor.y * scale) as i32,
-                    size.points() * scale,
-                    &font,
-                    text,
-                );
+/// Draws text over its plate, shadow and halo, in the same order as the
+/// canvas. `pos`, `layout` and `turn` are in image pixels, like on the canvas.
+#[allow(clippy::too_many_arguments)]
+fn draw_styled_text(
+    pixmap: &mut Pixmap,
+    font: FontChain,
+    pos: Point,
+    layout: &TextLayout,
+    size: TextSize,
+    effects: &TextEffects,
+    color: [u8; 4],
+    scale: f32,
+    turn: Turn,
+) {
+    let turn = Turn {
+        center: turn.center * scale,
+        ..turn
+    };
+    let blend = |pixmap: &mut Pixmap, (mask, left, top), color| {
+        let (mask, left, top) = turn_mask(mask, left, top, turn);
+        blend_mask(pixmap, &mask, left, top, color);
+    };
+    let x = pos.x * scale;
+    let y = pos.y * scale;
+    let font_size = size.points() * scale;
+
+    if effects.background {
+        let padding = TextEffects::plate_padding(size) * scale;
+        let plate_w = (layout.width * scale + padding * 2.0).ceil() as u32;
+        let plate_h = (layout.height * scale + padding * 2.0).ceil() as u32;
+        if let Some(mut mask) = Mask::new(plate_w.max(1), plate_h.max(1)) {
+            let mut pb = PathBuilder::new();
+            push_rounded_rect(&mut pb, 0.0, 0.0, plate_w as f32, plate_h as f32, padding);
+            if let Some(path) = pb.finish() {
+                mask.fill_path(&path, FillRule::Winding, true, Transform::identity());
+                if let Some(mask) = GrayImage::from_raw(plate_w, plate_h, mask.data().to_vec()) {
+                    blend(
+                        pixmap,
+                        (
+                            mask,
+                            (x - padding).round() as i32,
+                            (y - padding).round() as i32,
+                        ),
+                        effects.background_color,
+                    );
+                }
             }
-            _ => {}
         }
     }
-}
 
-fn arrow_text_anchor(from: Point, to: Point) -> Point {
-    let dx = to.x - from.x;
-    let dy = to.y - from.y;
-    let len = (dx * dx + dy * dy).sqrt().max(1.0);
-    let ux = dx / len;
-    let uy = dy / len;
-    let perp_a_x = -uy;
-    let perp_a_y = ux;
-    let perp_b_x = uy;
-    let perp_b_y = -ux;
-    let (perp_x, perp_y) = if perp_a_y < perp_b_y {
-        (perp_a_x, perp_a_y)
-    } else {
-        (perp_b_x, perp_b_y)
+    // Shadow and halo are stamped into coverage masks first, so translucent
+    // colours blend once instead of piling up where stamps overlap.
+    let offsets = effects.outline_offsets(scale);
+    let stamp = TextStamp {
+        font,
+        font_size,
+        layout,
+        x,
+        y,
+        scale,
+        reach: offsets
+            .iter()
+            .map(|offset| offset.length())
+            .fold(TextEffects::shadow_offset(size) * scale, f32::max),
     };
-    Point {
-        x: from.x + perp_x * 12.0 + 6.0,
-        y: from.y + perp_y * 12.0 - 2.0,
+    if effects.shadow {
+        let offset = TextEffects::shadow_offset(size) * scale;
+        blend(
+            pixmap,
+            stamp.coverage(&[Vec2::splat(offset)]),
+            TEXT_SHADOW_COLOR,
+        );
+    }
+    if !offsets.is_empty() {
+        blend(pixmap, stamp.coverage(&offsets), effects.outline_color);
     }
+    blend(pixmap, stamp.coverage(&[Vec2::ZERO]), color);
 }
 
-fn load_system_font() -> Option<FontArc> {
-    let candidates = [
-        "/System/Library/Fonts/Supplemental/Arial Unicode.ttf",
-        "/System/Library/Fonts/Supplemental/Arial.ttf",
-        "/System/Library/Fonts/SFNS.ttf",
-        "/System/Library/Fonts/Supplemental/Helvetica.ttf",
-    ];
-
-    for path in candidates {
-        if let Ok(bytes) = std::fs::read(path) {
-            if let Ok(font) = FontArc::try_from_vec(bytes) {
-                return Some(font);
-            }
+/// Turns a coverage mask whose top-left corner sits at (`left`, `top`) by
+/// `turn`, given in output pixels, and returns it with its new corner.
+fn turn_mask(mask: GrayImage, left: i32, top: i32, turn: Turn) -> (GrayImage, i32, i32) {
+    if turn.angle == 0.0 {
+        return (mask, left, top);
+    }
+    let (width, height) = (mask.width() as f32, mask.height() as f32);
+    let corners = [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)]
+        .map(|(dx, dy)| turn.apply(egui::pos2(left as f32 + dx, top as f32 + dy)));
+    let bounds = egui::Rect::from_points(&corners);
+    let (new_left, new_top) = (bounds.min.x.floor() as i32, bounds.min.y.floor() as i32);
+    let mut turned = GrayImage::new(
+        (bounds.max.x.ceil() as i32 - new_left).max(1) as u32,
+        (bounds.max.y.ceil() as i32 - new_top).max(1) as u32,
+    );
+    let coverage_at = |x: i32, y: i32| {
+        if x < 0 || y < 0 || x >= mask.width() as i32 || y >= mask.height() as i32 {
+            0.0
+        } else {
+            mask.get_pixel(x as u32, y as u32).0[0] as f32
         }
+    };
+    for (x, y, pixel) in turned.enumerate_pixels_mut() {
+        // Sample the upright mask bilinearly under each pixel centre.
+        let source = turn.invert(egui::pos2(
+            (new_left + x as i32) as f32 + 0.5,
+            (new_top + y as i32) as f32 + 0.5,
+        ));
+        let sx = source.x - left as f32 - 0.5;
+        let sy = source.y - top as f32 - 0.5;
+        let (x0, y0) = (sx.floor(), sy.floor());
+        let (fx, fy) = (sx - x0, sy - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let upper = coverage_at(x0, y0) * (1.0 - fx) + coverage_at(x0 + 1, y0) * fx;
+        let lower = coverage_at(x0, y0 + 1) * (1.0 - fx) + coverage_at(x0 + 1, y0 + 1) * fx;
+        pixel.0[0] = (upper * (1.0 - fy) + lower * fy).round() as u8;
     }
+    (turned, new_left, new_top)
+}
 
-    None
+/// Source-over blends `color` into the premultiplied `pixmap` through an
+/// 8-bit coverage mask whose top-left corner sits at (`left`, `top`).
+fn blend_mask(pixmap: &mut Pixmap, mask: &GrayImage, left: i32, top: i32, color: [u8; 4]) {
+    let (width, height) = (pixmap.width() as i32, pixmap.height() as i32);
+    let data = pixmap.data_mut();
+    for (mx, my, coverage) in mask.enumerate_pixels() {
+        let alpha = coverage.0[0] as f32 / 255.0 * color[3] as f32 / 255.0;
+        if alpha <= 0.0 {
+            continue;
+        }
+        let (px, py) = (left + mx as i32, top + my as i32);
+        if px < 0 || py < 0 || px >= width || py >= height {
+            continue;
+        }
+        let index = (py * width + px) as usize * 4;
+        let pixel = &mut data[index..index + 4];
+        let out_alpha = (alpha * 255.0 + pixel[3] as f32 * (1.0 - alpha)).round();
+        for (value, source) in pixel.iter_mut().zip(color).take(3) {
+            let blended = source as f32 * alpha + *value as f32 * (1.0 - alpha);
+            // Premultiplied channels never exceed alpha.
+            *value = blended.round().min(out_alpha) as u8;
+        }
+        pixel[3] = out_alpha as u8;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use image::{DynamicImage, RgbaImage};
 
-    use super::flatten;
-    use crate::annotation::{Annotation, AnnotationKind, Point, RectData, StrokeWidth};
+    use tiny_skia::{LineCap, Stroke};
+
+    use super::{flatten, styled_stroke};
+    use crate::annotation::{
+        Annotation, AnnotationKind, LoupeShape, Point, RectData, StrokeStyle, StrokeWidth, TextSize,
+    };
+
+    #[test]
+    fn text_renders_without_system_fonts_and_reports_missing_glyphs() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            160,
+            60,
+            image::Rgba([255, 255, 255, 255]),
+        ));
+        let text = |content: &str| {
+            let kind = serde_json::json!({
+                "Text": {"pos": {"x": 10.0, "y": 10.0}, "content": content, "size": 24}
+            });
+            vec![Annotation {
+                id: 1,
+                kind: serde_json::from_value(kind).expect("text"),
+                color: [0, 0, 0, 255],
+                stroke_width: StrokeWidth::MEDIUM,
+                stroke_style: StrokeStyle::Solid,
+                rotation: 0.0,
+                group: None,
+            }]
+        };
+
+        let result = flatten(&image, &text("Привет ✔"), 1.0)
+            .expect("bundled fonts cover Cyrillic and symbols")
+            .to_rgba8();
+        let inked = result.pixels().filter(|pixel| pixel.0[0] < 128).count();
+        assert!(inked > 50, "{inked} dark pixels");
+
+        let error = flatten(&image, &text("\u{10000}"), 1.0).expect_err("no font has Linear B");
+        assert!(error.to_string().contains("U+10000"), "{error}");
+    }
+
+    #[test]
+    fn text_follows_the_draw_order_of_its_annotation() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            80,
+            60,
+            image::Rgba([255, 255, 255, 255]),
+        ));
+        let annotation = |id, kind, color| Annotation {
+            id,
+            kind,
+            color,
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        };
+        let step = annotation(
+            1,
+            AnnotationKind::Step {
+                center: Point::new(40.0, 30.0),
+                number: 8,
+                size: TextSize::L,
+            },
+            [0, 0, 0, 255],
+        );
+        let cover = annotation(
+            2,
+            AnnotationKind::Rectangle {
+                rect: RectData {
+                    min: Point::new(10.0, 5.0),
+                    max: Point::new(70.0, 55.0),
+                },
+                fill: Some([0, 0, 0, 255]),
+                corner_radius: 0.0,
+            },
+            [0, 0, 0, 255],
+        );
+        let light_pixels = |annotations: &[Annotation]| {
+            let result = flatten(&image, annotations, 1.0)
+                .expect("flatten should succeed")
+                .to_rgba8();
+            (25..55)
+                .flat_map(|y| (25..55).map(move |x| (x, y)))
+                .filter(|&(x, y)| result.get_pixel(x, y).0[0] > 128)
+                .count()
+        };
+
+        // The white number shows on the black badge, until a filled
+        // rectangle stacked above it covers the badge, number and all.
+        assert!(light_pixels(&[cover.clone(), step.clone()]) > 10);
+        assert_eq!(light_pixels(&[step, cover]), 0);
+    }
+
+    #[test]
+    fn exported_text_fills_exactly_its_selection_bounds() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            200,
+            100,
+            image::Rgba([255, 255, 255, 255]),
+        ));
+        let kind = serde_json::json!({
+            "Text": {"pos": {"x": 12.0, "y": 8.0}, "content": "Wide text\nline two", "size": 18}
+        });
+        let annotation = Annotation {
+            id: 1,
+            kind: serde_json::from_value(kind).expect("text"),
+            color: [0, 0, 0, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        };
+        let bounds = annotation.bounds();
+        let scale = 2.0;
+
+        let result = flatten(&image, &[annotation], scale)
+            .expect("flatten should succeed")
+            .to_rgba8();
+        let (mut min_x, mut max_x, mut max_y) = (u32::MAX, 0, 0);
+        for (x, y, pixel) in result.enumerate_pixels() {
+            if pixel.0[0] < 200 {
+                min_x = min_x.min(x);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
+        }
+        assert!(
+            min_x as f32 >= bounds.min.x * scale - 1.0,
+            "{min_x} vs {bounds:?}"
+        );
+        assert!(
+            max_x as f32 <= bounds.max.x * scale + 1.0,
+            "{max_x} vs {bounds:?}"
+        );
+        assert!(
+            max_x as f32 >= bounds.max.x * scale - 6.0,
+            "{max_x} vs {bounds:?}"
+        );
+        assert!(
+            max_y as f32 <= bounds.max.y * scale,
+            "{max_y} vs {bounds:?}"
+        );
+    }
 
     #[test]
     fn flatten_keeps_image_size() {
@@ -253,13 +1023,141 @@ mod tests {
                     min: Point { x: 8.0, y: 8.0 },
                     max: Point { x: 120.0, y: 80.0 },
                 },
+                fill: None,
+                corner_radius: 0.0,
             },
             color: [229, 62, 62, 255],
-            stroke_width: StrokeWidth::Medium,
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
         }];
 
         let result = flatten(&image, &annotations, 1.0).expect("flatten should succeed");
         assert_eq!(result.width(), 320);
         assert_eq!(result.height(), 200);
     }
+
+    #[test]
+    fn highlighter_multiplies_so_dark_pixels_stay_dark() {
+        let mut base = RgbaImage::from_pixel(64, 32, image::Rgba([255, 255, 255, 255]));
+        base.put_pixel(32, 16, image::Rgba([0, 0, 0, 255]));
+        let image = DynamicImage::ImageRgba8(base);
+        let annotations = vec![Annotation {
+            id: 1,
+            kind: AnnotationKind::Highlighter {
+                points: vec![Point { x: 4.0, y: 16.0 }, Point { x: 60.0, y: 16.0 }],
+            },
+            color: [255, 226, 61, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        }];
+
+        let result = flatten(&image, &annotations, 1.0)
+            .expect("flatten should succeed")
+            .to_rgba8();
+        assert_eq!(result.get_pixel(32, 16).0, [0, 0, 0, 255]);
+        assert_eq!(result.get_pixel(20, 16).0, [255, 226, 61, 255]);
+        assert_eq!(result.get_pixel(20, 2).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn dashed_outline_leaves_gaps_along_the_edge() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            120,
+            60,
+            image::Rgba([255, 255, 255, 255]),
+        ));
+        let rectangle = AnnotationKind::Rectangle {
+            rect: RectData {
+                min: Point::new(10.0, 10.0),
+                max: Point::new(110.0, 50.0),
+            },
+            fill: None,
+            corner_radius: 0.0,
+        };
+        let freehand = AnnotationKind::Freehand {
+            points: (1..=11)
+                .map(|i| Point::new(i as f32 * 10.0, 10.0))
+                .collect(),
+        };
+        let outline = |kind: &AnnotationKind, stroke_style| {
+            let annotations = vec![Annotation {
+                id: 1,
+                kind: kind.clone(),
+                color: [0, 0, 0, 255],
+                stroke_width: StrokeWidth::MEDIUM,
+                stroke_style,
+                rotation: 0.0,
+                group: None,
+            }];
+            let result = flatten(&image, &annotations, 1.0)
+                .expect("flatten should succeed")
+                .to_rgba8();
+            (20..100)
+                .filter(|x| result.get_pixel(*x, 10).0[0] > 128)
+                .count()
+        };
+
+        for kind in [&rectangle, &freehand] {
+            assert_eq!(outline(kind, StrokeStyle::Solid), 0);
+            let dashed = outline(kind, StrokeStyle::Dashed);
+            assert!(dashed > 20 && dashed < 50, "{dashed} light pixels");
+            let dotted = outline(kind, StrokeStyle::Dotted);
+            assert!(dotted > 20 && dotted < 60, "{dotted} light pixels");
+        }
+        let dots = styled_stroke(&Stroke::default(), StrokeStyle::Dotted);
+        assert_eq!(dots.line_cap, LineCap::Round);
+    }
+
+    #[test]
+    fn magnifier_enlarges_source_pixels_into_the_loupe() {
+        let mut base = RgbaImage::from_pixel(200, 100, image::Rgba([255, 255, 255, 255]));
+        for y in 20..30 {
+            for x in 20..30 {
+                base.put_pixel(x, y, image::Rgba([20, 40, 200, 255]));
+            }
+        }
+        let image = DynamicImage::ImageRgba8(base);
+        let annotations = vec![Annotation {
+            id: 1,
+            kind: AnnotationKind::Magnifier {
+                source: RectData {
+                    min: Point { x: 15.0, y: 15.0 },
+                    max: Point { x: 35.0, y: 35.0 },
+                },
+                target: RectData {
+                    min: Point { x: 100.0, y: 10.0 },
+                    max: Point { x: 180.0, y: 90.0 },
+                },
+                shape: LoupeShape::Circle,
+                connector: true,
+            },
+            color: [229, 62, 62, 255],
+            stroke_width: StrokeWidth::THIN,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        }];
+
+        let result = flatten(&image, &annotations, 1.0)
+            .expect("flatten should succeed")
+            .to_rgba8();
+        // The 10px square covers the middle half of the 4x loupe; bicubic
+        // resampling may shift channels slightly.
+        let close = |x: u32, y: u32, expected: [u8; 4]| {
+            let pixel = result.get_pixel(x, y).0;
+            pixel
+                .iter()
+                .zip(expected)
+                .all(|(got, want)| got.abs_diff(want) <= 4)
+        };
+        assert!(close(140, 50, [20, 40, 200, 255]));
+        assert!(close(125, 35, [20, 40, 200, 255]));
+        assert!(close(110, 50, [255, 255, 255, 255]));
+        // Corners of the target box lie outside the round frame.
+        assert_eq!(result.get_pixel(101, 11).0, [255, 255, 255, 255]);
+    }
 }
@@ -0,0 +1,437 @@
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use ab_glyph::{Font, FontArc};
+use anyhow::{bail, Result};
+use egui::{FontData, FontDefinitions, FontFamily};
+
+use crate::annotation::TextFont;
+
+/// Family used by legacy annotations and whenever a saved family is not
+/// installed on this machine.
+pub const DEFAULT_FAMILY: &str = "Sans";
+
+/// Regular, bold, italic and bold italic files for one system family. The
+/// first path that exists wins, so one entry covers macOS, Windows and Linux.
+struct SystemFamily {
+    name: &'static str,
+    faces: [&'static [&'static str]; 4],
+}
+
+const SYSTEM_FAMILIES: &[SystemFamily] = &[
+    SystemFamily {
+        name: "Arial",
+        faces: [
+            &[
+                "/System/Library/Fonts/Supplemental/Arial.ttf",
+                "C:\\Windows\\Fonts\\arial.ttf",
+                "/usr/share/fonts/truetype/msttcorefonts/Arial.ttf",
+            ],
+            &[
+                "/System/Library/Fonts/Supplemental/Arial Bold.ttf",
+                "C:\\Windows\\Fonts\\arialbd.ttf",
+                "/usr/share/fonts/truetype/msttcorefonts/Arial_Bold.ttf",
+            ],
+            &[
+                "/System/Library/Fonts/Supplemental/Arial Italic.ttf",
+                "C:\\Windows\\Fonts\\ariali.ttf",
+                "/usr/share/fonts/truetype/msttcorefonts/Arial_Italic.ttf",
+            ],
+            &[
+                "/System/Library/Fonts/Supplemental/Arial Bold Italic.ttf",
+                "C:\\Windows\\Fonts\\arialbi.ttf",
+                "/usr/share/fonts/truetype/msttcorefonts/Arial_Bold_Italic.ttf",
+            ],
+        ],
+    },
+    SystemFamily {
+        name: "Verdana",
+        faces: [
+            &[
+                "/System/Library/Fonts/Supplemental/Verdana.ttf",
+                "C:\\Windows\\Fonts\\verdana.ttf",
+            ],
+            &[
+                "/System/Library/Fonts/Supplemental/Verdana Bold.ttf",
+                "C:\\Windows\\Fonts\\verdanab.ttf",
+            ],
+            &[
+                "/System/Library/Fonts/Supplemental/Verdana Italic.ttf",
+                "C:\\Windows\\Fonts\\verdanai.ttf",
+            ],
+            &[
+                "/System/Library/Fonts/Supplemental/Verdana Bold Italic.ttf",
+                "C:\\Windows\\Fonts\\verdanaz.ttf",
+            ],
+        ],
+    },
+    SystemFamily {
+        name: "Georgia",
+        faces: [
+            &[
+                "/System/Library/Fonts/Supplemental/Georgia.ttf",
+                "C:\\Windows\\Fonts\\georgia.ttf",
+            ],
+            &[
+                "/System/Library/Fonts/Supplemental/Georgia Bold.ttf",
+                "C:\\Windows\\Fonts\\georgiab.ttf",
+            ],
+            &[
+                "/System/Library/Fonts/Supplemental/Georgia Italic.ttf",
+                "C:\\Windows\\Fonts\\georgiai.ttf",
+            ],
+            &[
+                "/System/Library/Fonts/Supplemental/Georgia Bold Italic.ttf",
+                "C:\\Windows\\Fonts\\georgiaz.ttf",
+            ],
+        ],
+    },
+    SystemFamily {
+        name: "Courier New",
+        faces: [
+            &[
+                "/System/Library/Fonts/Supplemental/Courier New.ttf",
+                "C:\\Windows\\Fonts\\cour.ttf",
+            ],
+            &[
+                "/System/Library/Fonts/Supplemental/Courier New Bold.ttf",
+                "C:\\Windows\\Fonts\\courbd.ttf",
+            ],
+            &[
+                "/System/Library/Fonts/Supplemental/Courier New Italic.ttf",
+                "C:\\Windows\\Fonts\\couri.ttf",
+            ],
+            &[
+                "/System/Library/Fonts/Supplemental/Courier New Bold Italic.ttf",
+                "C:\\Windows\\Fonts\\courbi.ttf",
+            ],
+        ],
+    },
+    SystemFamily {
+        name: "DejaVu Sans",
+        faces: [
+            &[
+                "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+                "/usr/share/fonts/dejavu-sans-fonts/DejaVuSans.ttf",
+            ],
+            &[
+                "/usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf",
+                "/usr/share/fonts/dejavu-sans-fonts/DejaVuSans-Bold.ttf",
+            ],
+            &[
+                "/usr/share/fonts/truetype/dejavu/DejaVuSans-Oblique.ttf",
+                "/usr/share/fonts/dejavu-sans-fonts/DejaVuSans-Oblique.ttf",
+            ],
+            &[
+                "/usr/share/fonts/truetype/dejavu/DejaVuSans-BoldOblique.ttf",
+                "/usr/share/fonts/dejavu-sans-fonts/DejaVuSans-BoldOblique.ttf",
+            ],
+        ],
+    },
+    SystemFamily {
+        name: "DejaVu Serif",
+        faces: [
+            &[
+                "/usr/share/fonts/truetype/dejavu/DejaVuSerif.ttf",
+                "/usr/share/fonts/dejavu-serif-fonts/DejaVuSerif.ttf",
+            ],
+            &[
+                "/usr/share/fonts/truetype/dejavu/DejaVuSerif-Bold.ttf",
+                "/usr/share/fonts/dejavu-serif-fonts/DejaVuSerif-Bold.ttf",
+            ],
+            &[
+                "/usr/share/fonts/truetype/dejavu/DejaVuSerif-Italic.ttf",
+                "/usr/share/fonts/dejavu-serif-fonts/DejaVuSerif-Italic.ttf",
+            ],
+            &[
+                "/usr/share/fonts/truetype/dejavu/DejaVuSerif-BoldItalic.ttf",
+                "/usr/share/fonts/dejavu-serif-fonts/DejaVuSerif-BoldItalic.ttf",
+            ],
+        ],
+    },
+    SystemFamily {
+        name: "Liberation Sans",
+        faces: [
+            &["/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf"],
+            &["/usr/share/fonts/truetype/liberation/LiberationSans-Bold.ttf"],
+            &["/usr/share/fonts/truetype/liberation/LiberationSans-Italic.ttf"],
+            &["/usr/share/fonts/truetype/liberation/LiberationSans-BoldItalic.ttf"],
+        ],
+    },
+];
+
+/// System fonts consulted for glyphs the chosen face and the bundled fonts
+/// lack. Each group covers one set of scripts; only the first file found in a
+/// group is loaded, since CJK fonts run to tens of megabytes.
+const SYSTEM_FALLBACKS: &[&[&str]] = &[
+    // CJK.
+    &[
+        "/System/Library/Fonts/Supplemental/Arial Unicode.ttf",
+        "/Library/Fonts/Arial Unicode.ttf",
+        "/System/Library/Fonts/Hiragino Sans GB.ttc",
+        "C:\\Windows\\Fonts\\msyh.ttc",
+        "C:\\Windows\\Fonts\\simsun.ttc",
+        "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+        "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+        "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+        "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
+        "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    ],
+    // Korean, which the Chinese fonts above leave out.
+    &[
+        "/System/Library/Fonts/AppleSDGothicNeo.ttc",
+        "C:\\Windows\\Fonts\\malgun.ttf",
+    ],
+    // Extended Latin, Greek and Cyrillic beyond the bundled sans.
+    &[
+        "/System/Library/Fonts/Supplemental/Arial.ttf",
+        "C:\\Windows\\Fonts\\segoeui.ttf",
+        "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+        "/usr/share/fonts/dejavu-sans-fonts/DejaVuSans.ttf",
+    ],
+];
+
+const STYLE_NAMES: [&str; 4] = ["Regular", "Bold", "Italic", "Bold Italic"];
+
+/// One loaded face. `key` names it both in egui's font definitions and as
+/// its egui font family.
+pub struct Face {
+    key: String,
+    bytes: &'static [u8],
+    font: FontArc,
+}
+
+pub struct Family {
+    pub name: String,
+    faces: [Option<Face>; 4],
+}
+
+impl Family {
+    pub fn has_style(&self, bold: bool, italic: bool) -> bool {
+        self.faces[style_index(bold, italic)].is_some()
+    }
+
+    fn face(&self, bold: bool, italic: bool) -> &Face {
+        self.faces[style_index(bold, italic)]
+            .as_ref()
+            .or(self.faces[0].as_ref())
+            .expect("every family has a regular face")
+    }
+}
+
+fn style_index(bold: bool, italic: bool) -> usize {
+    usize::from(bold) | usize::from(italic) << 1
+}
+
+/// Families available on this machine: the sans and monospace faces built
+/// into the binary, followed by whichever system families are installed.
+pub fn families() -> &'static [Family] {
+    static FAMILIES: OnceLock<Vec<Family>> = OnceLock::new();
+    FAMILIES.get_or_init(|| {
+        let definitions = FontDefinitions::default();
+        let mut families = Vec::new();
+        for (name, egui_family) in [
+            (DEFAULT_FAMILY, FontFamily::Proportional),
+            ("Mono", FontFamily::Monospace),
+        ] {
+            let face = definitions
+                .families
+                .get(&egui_family)
+                .and_then(|names| names.first())
+                .and_then(|key| bundled_face(&definitions, key, format!("{name} Regular")));
+            if let Some(face) = face {
+                families.push(Family {
+                    name: name.to_string(),
+                    faces: [Some(face), None, None, None],
+                });
+            }
+        }
+        for system in SYSTEM_FAMILIES {
+            let mut faces: [Option<Face>; 4] = Default::default();
+            for ((slot, paths), style) in faces.iter_mut().zip(system.faces).zip(STYLE_NAMES) {
+                *slot = paths.iter().find_map(|path| {
+                    // Loaded once and kept for the whole session, like the
+                    // bundled fonts.
+                    let bytes = Box::leak(std::fs::read(path).ok()?.into_boxed_slice());
+                    load_face(format!("{} {style}", system.name), bytes)
+                });
+            }
+            if faces[0].is_some() {
+                families.push(Family {
+                    name: system.name.to_string(),
+                    faces,
+                });
+            }
+        }
+        families
+    })
+}
+
+/// Faces tried, in order, for glyphs the chosen face lacks: the bundled sans,
+/// the bundled emoji fonts, then one system font per script group.
+fn fallbacks() -> &'static [Face] {
+    static FALLBACKS: OnceLock<Vec<Face>> = OnceLock::new();
+    FALLBACKS.get_or_init(|| {
+        let definitions = FontDefinitions::default();
+        let bundled = definitions
+            .families
+            .get(&FontFamily::Proportional)
+            .into_iter()
+            .flatten()
+            .filter_map(|key| bundled_face(&definitions, key, format!("Fallback {key}")));
+        let system = SYSTEM_FALLBACKS.iter().filter_map(|paths| {
+            paths.iter().find_map(|path| {
+                let bytes = Box::leak(std::fs::read(path).ok()?.into_boxed_slice());
+                load_face(format!("Fallback {path}"), bytes)
+            })
+        });
+        bundled.chain(system).collect()
+    })
+}
+
+/// A face compiled into the binary with egui's default fonts.
+fn bundled_face(definitions: &FontDefinitions, key: &str, name: String) -> Option<Face> {
+    let bytes = match &definitions.font_data.get(key)?.font {
+        Cow::Borrowed(bytes) => *bytes,
+        Cow::Owned(bytes) => Box::leak(bytes.clone().into_boxed_slice()),
+    };
+    load_face(name, bytes)
+}
+
+fn load_face(key: String, bytes: &'static [u8]) -> Option<Face> {
+    let font = FontArc::try_from_slice(bytes).ok()?;
+    Some(Face { key, bytes, font })
+}
+
+/// The installed family called `name`, or the bundled sans.
+pub fn family(name: &str) -> &'static Family {
+    let families = families();
+    families
+        .iter()
+        .find(|family| family.name == name)
+        .or_else(|| families.first())
+        .expect("egui ships a bundled font")
+}
+
+fn face(font: &TextFont) -> &'static Face {
+    family(&font.family).face(font.bold, font.italic)
+}
+
+/// The chosen face followed by the fallback faces, in the order egui
+/// searches them on screen.
+#[derive(Clone, Copy)]
+pub struct FontChain {
+    primary: &'static FontArc,
+}
+
+impl FontChain {
+    /// Face whose metrics set the line height and baseline.
+    pub fn primary(self) -> &'static FontArc {
+        self.primary
+    }
+
+    /// First face in the chain with a glyph for `ch`.
+    pub fn font_for(self, ch: char) -> Option<&'static FontArc> {
+        std::iter::once(self.primary)
+            .chain(fallbacks().iter().map(|face| &face.font))
+            .find(|font| font.glyph_id(ch).0 != 0)
+    }
+
+    /// Reports the first visible character that no face in the chain can
+    /// draw, so the export can refuse instead of dropping it.
+    pub fn ensure_covers(self, text: &str) -> Result<()> {
+        match text
+            .chars()
+            .find(|ch| !ch.is_whitespace() && !ch.is_control() && self.font_for(*ch).is_none())
+        {
+            Some(ch) => bail!(
+                "no bundled or installed font has a glyph for '{ch}' (U+{:04X}); install a font \
+                 that covers it or remove it from the text",
+                ch as u32
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Glyphs used to measure and export `font`.
+pub fn font_chain(font: &TextFont) -> FontChain {
+    FontChain {
+        primary: &face(font).font,
+    }
+}
+
+/// The egui family that paints `font` on the canvas. Only valid once
+/// [`install`] has run.
+pub fn egui_family(font: &TextFont) -> FontFamily {
+    FontFamily::Name(face(font).key.as_str().into())
+}
+
+/// egui's default definitions plus one named family per loaded face, each
+/// followed by the same fallback faces [`FontChain`] searches.
+fn font_definitions() -> FontDefinitions {
+    let mut definitions = FontDefinitions::default();
+    for face in fallbacks() {
+        definitions
+            .font_data
+            .insert(face.key.clone(), FontData::from_static(face.bytes));
+    }
+    for face in families()
+        .iter()
+        .flat_map(|family| family.faces.iter().flatten())
+    {
+        definitions
+            .font_data
+            .insert(face.key.clone(), FontData::from_static(face.bytes));
+        let mut chain = vec![face.key.clone()];
+        chain.extend(fallbacks().iter().map(|face| face.key.clone()));
+        definitions
+            .families
+            .insert(FontFamily::Name(face.key.as_str().into()), chain);
+    }
+    definitions
+}
+
+pub fn install(ctx: &egui::Context) {
+    ctx.set_fonts(font_definitions());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{egui_family, families, font_chain, font_definitions, DEFAULT_FAMILY};
+    use crate::annotation::TextFont;
+
+    #[test]
+    fn unknown_families_and_missing_styles_fall_back_to_an_installed_face() {
+        let bundled = families()
+            .iter()
+            .find(|family| family.name == DEFAULT_FAMILY)
+            .expect("bundled family");
+        assert!(bundled.has_style(false, false));
+        assert!(!bundled.has_style(true, true));
+
+        let missing = TextFont {
+            family: "Not A Real Font".to_string(),
+            bold: true,
+            italic: true,
+        };
+        let default = TextFont::default();
+        assert!(std::ptr::eq(
+            font_chain(&missing).primary(),
+            font_chain(&default).primary()
+        ));
+        assert_eq!(egui_family(&missing), egui_family(&default));
+
+        let definitions = font_definitions();
+        for family in families() {
+            for (bold, italic) in [(false, false), (true, false), (false, true), (true, true)] {
+                let font = TextFont {
+                    family: family.name.clone(),
+                    bold,
+                    italic,
+                };
+                assert!(definitions.families.contains_key(&egui_family(&font)));
+            }
+        }
+    }
+}
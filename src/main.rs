@@ -4,9 +4,14 @@ mod app;
 mod canvas;
 mod clipboard;
 mod flatten;
+mod fonts;
 mod history;
 mod platform;
+mod redaction;
+mod spotlight;
+mod stamp;
 mod state;
+mod text_layout;
 mod theme;
 mod toolbar;
 mod ui_controls;
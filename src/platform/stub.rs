@@ -1,3 +1,4 @@
+use anyhow::Result;
 use arboard::Clipboard;
 use eframe::Frame;
 
@@ -0,0 +1,161 @@
+use anyhow::{anyhow, Result};
+use image::RgbaImage;
+use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Rect, Transform};
+
+use crate::annotation::{Annotation, AnnotationKind, RectData, SpotlightShape};
+
+/// All spotlight regions of an image merged into one mask. Everything outside
+/// the regions is dimmed, and optionally desaturated, by the same amount.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spotlight {
+    pub regions: Vec<(RectData, SpotlightShape)>,
+    pub dim: f32,
+    pub desaturate: bool,
+}
+
+impl Spotlight {
+    /// Collects the spotlight annotations, or `None` when there are none.
+    /// The style is image-wide; should regions disagree, the latest one wins.
+    pub fn from_annotations(annotations: &[Annotation]) -> Option<Self> {
+        let mut spotlight: Option<Self> = None;
+        for annotation in annotations {
+            let AnnotationKind::Spotlight {
+                rect,
+                shape,
+                dim,
+                desaturate,
+            } = &annotation.kind
+            else {
+                continue;
+            };
+            let merged = spotlight.get_or_insert(Self {
+                regions: Vec::new(),
+                dim: 0.0,
+                desaturate: false,
+            });
+            merged.regions.push((rect.normalize(), *shape));
+            merged.dim = dim.clamp(0.0, 1.0);
+            merged.desaturate = *desaturate;
+        }
+        spotlight
+    }
+
+    /// Colour of a pixel that lies fully outside every region.
+    pub fn dimmed_pixel(&self, pixel: [u8; 4]) -> [u8; 4] {
+        let keep = 1.0 - self.dim;
+        let [r, g, b, a] = pixel;
+        if self.desaturate {
+            let value = (luminance(pixel) * keep).round() as u8;
+            [value, value, value, a]
+        } else {
+            [
+                (r as f32 * keep).round() as u8,
+                (g as f32 * keep).round() as u8,
+                (b as f32 * keep).round() as u8,
+                a,
+            ]
+        }
+    }
+
+    /// Dims `image` outside the regions. Region edges are anti-aliased so the
+    /// export matches the smooth outline shown on the canvas.
+    pub fn apply(&self, image: &mut RgbaImage, scale: f32) -> Result<()> {
+        let mut mask = Pixmap::new(image.width(), image.height())
+            .ok_or_else(|| anyhow!("cannot allocate spotlight mask"))?;
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(255, 255, 255, 255);
+        paint.anti_alias = true;
+        for (rect, shape) in &self.regions {
+            let Some(bounds) = Rect::from_ltrb(
+                rect.min.x * scale,
+                rect.min.y * scale,
+                rect.max.x * scale,
+                rect.max.y * scale,
+            ) else {
+                continue;
+            };
+            let path = match shape {
+                SpotlightShape::Rectangle => Some(PathBuilder::from_rect(bounds)),
+                SpotlightShape::Ellipse => PathBuilder::from_oval(bounds),
+            };
+            if let Some(path) = path {
+                mask.fill_path(
+                    &path,
+                    &paint,
+                    FillRule::Winding,
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+
+        for (pixel, coverage) in image.pixels_mut().zip(mask.pixels()) {
+            let outside = 1.0 - coverage.alpha() as f32 / 255.0;
+            if outside <= 0.0 {
+                continue;
+            }
+            let dimmed = self.dimmed_pixel(pixel.0);
+            for (value, target) in pixel.0.iter_mut().zip(dimmed).take(3) {
+                let original = *value as f32;
+                *value = (original + (target as f32 - original) * outside).round() as u8;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Greyscale copy of `image` using the same weights as the dimmed output.
+pub fn desaturated(image: &RgbaImage) -> RgbaImage {
+    let mut gray = image.clone();
+    for pixel in gray.pixels_mut() {
+        let value = luminance(pixel.0).round() as u8;
+        pixel.0 = [value, value, value, pixel.0[3]];
+    }
+    gray
+}
+
+fn luminance(pixel: [u8; 4]) -> f32 {
+    0.2126 * pixel[0] as f32 + 0.7152 * pixel[1] as f32 + 0.0722 * pixel[2] as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::Spotlight;
+    use crate::annotation::{Point, RectData, SpotlightShape};
+
+    #[test]
+    fn overlapping_regions_share_one_mask() {
+        let mut image = RgbaImage::from_pixel(60, 20, Rgba([200, 100, 50, 255]));
+        let spotlight = Spotlight {
+            regions: vec![
+                (
+                    RectData {
+                        min: Point::new(0.0, 0.0),
+                        max: Point::new(30.0, 20.0),
+                    },
+                    SpotlightShape::Rectangle,
+                ),
+                (
+                    RectData {
+                        min: Point::new(20.0, 0.0),
+                        max: Point::new(40.0, 20.0),
+                    },
+                    SpotlightShape::Rectangle,
+                ),
+            ],
+            dim: 0.5,
+            desaturate: true,
+        };
+
+        spotlight.apply(&mut image, 1.0).expect("spotlight applies");
+
+        assert_eq!(image.get_pixel(10, 10).0, [200, 100, 50, 255]);
+        assert_eq!(image.get_pixel(25, 10).0, [200, 100, 50, 255]);
+        assert_eq!(image.get_pixel(35, 10).0, [200, 100, 50, 255]);
+        let outside = image.get_pixel(50, 10).0;
+        assert_eq!(outside[0], outside[1]);
+        assert!(outside[0] < 100);
+    }
+}
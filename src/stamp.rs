@@ -0,0 +1,243 @@
+use egui::{Pos2, Rect};
+
+use crate::annotation::{rounded_rect_outline, StampIcon, Turn};
+
+/// Which colour a part of a stamp is painted in: the annotation colour, or
+/// the contrasting colour used for step numbers so marks stay readable on
+/// the filled shapes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ink {
+    Color,
+    Contrast,
+}
+
+/// One drawing primitive of a stamp, already placed in image pixels. Both
+/// the canvas and the export paint stamps from these, in order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StampPart {
+    /// Polyline with round caps and joins.
+    Stroke {
+        points: Vec<Pos2>,
+        width: f32,
+        ink: Ink,
+    },
+    /// Closed polyline with round joins.
+    Outline {
+        points: Vec<Pos2>,
+        width: f32,
+        ink: Ink,
+    },
+    /// Filled convex polygon; concave shapes are split into several.
+    Fill {
+        points: Vec<Pos2>,
+        ink: Ink,
+    },
+    Circle {
+        center: Pos2,
+        radius: f32,
+        ink: Ink,
+    },
+}
+
+impl StampPart {
+    /// The part rotated by `turn`; widths and radii are unchanged.
+    pub fn turned(self, turn: Turn) -> StampPart {
+        let turn_all = |points: Vec<Pos2>| points.into_iter().map(|p| turn.apply(p)).collect();
+        match self {
+            StampPart::Stroke { points, width, ink } => StampPart::Stroke {
+                points: turn_all(points),
+                width,
+                ink,
+            },
+            StampPart::Outline { points, width, ink } => StampPart::Outline {
+                points: turn_all(points),
+                width,
+                ink,
+            },
+            StampPart::Fill { points, ink } => StampPart::Fill {
+                points: turn_all(points),
+                ink,
+            },
+            StampPart::Circle {
+                center,
+                radius,
+                ink,
+            } => StampPart::Circle {
+                center: turn.apply(center),
+                radius,
+                ink,
+            },
+        }
+    }
+}
+
+/// The icon's parts fitted into the largest square centred in `rect`, so
+/// stamps keep their proportions however the box is dragged.
+pub fn stamp_parts(icon: StampIcon, rect: Rect) -> Vec<StampPart> {
+    let side = rect.width().abs().min(rect.height().abs());
+    let square = Rect::from_center_size(rect.center(), egui::Vec2::splat(side));
+    let flip = icon == StampIcon::ThumbsDown;
+    let at = |x: f32, y: f32| {
+        let y = if flip { 1.0 - y } else { y };
+        square.min + egui::vec2(x, y) * side
+    };
+    // Mirroring reverses the winding, which egui's polygon fill cares about.
+    let points = |coords: &[(f32, f32)]| {
+        let mut placed: Vec<Pos2> = coords.iter().map(|&(x, y)| at(x, y)).collect();
+        if flip {
+            placed.reverse();
+        }
+        placed
+    };
+    let stroke = |coords: &[(f32, f32)], width: f32, ink| StampPart::Stroke {
+        points: points(coords),
+        width: width * side,
+        ink,
+    };
+    let circle = |x, y, radius: f32, ink| StampPart::Circle {
+        center: at(x, y),
+        radius: radius * side,
+        ink,
+    };
+
+    match icon {
+        StampIcon::Check => vec![stroke(
+            &[(0.14, 0.54), (0.40, 0.78), (0.86, 0.24)],
+            0.16,
+            Ink::Color,
+        )],
+        StampIcon::Cross => vec![
+            stroke(&[(0.2, 0.2), (0.8, 0.8)], 0.16, Ink::Color),
+            stroke(&[(0.8, 0.2), (0.2, 0.8)], 0.16, Ink::Color),
+        ],
+        StampIcon::Warning => {
+            let triangle = [(0.5, 0.1), (0.93, 0.87), (0.07, 0.87)];
+            vec![
+                StampPart::Fill {
+                    points: points(&triangle),
+                    ink: Ink::Color,
+                },
+                // A thick outline in the same colour rounds the corners.
+                StampPart::Outline {
+                    points: points(&triangle),
+                    width: 0.08 * side,
+                    ink: Ink::Color,
+                },
+                stroke(&[(0.5, 0.38), (0.5, 0.6)], 0.09, Ink::Contrast),
+                circle(0.5, 0.74, 0.055, Ink::Contrast),
+            ]
+        }
+        StampIcon::Info => vec![
+            circle(0.5, 0.5, 0.46, Ink::Color),
+            circle(0.5, 0.28, 0.065, Ink::Contrast),
+            stroke(&[(0.5, 0.44), (0.5, 0.74)], 0.11, Ink::Contrast),
+        ],
+        StampIcon::Question => vec![
+            circle(0.5, 0.5, 0.46, Ink::Color),
+            stroke(
+                &[
+                    (0.36, 0.36),
+                    (0.38, 0.28),
+                    (0.44, 0.23),
+                    (0.52, 0.22),
+                    (0.6, 0.25),
+                    (0.64, 0.32),
+                    (0.62, 0.4),
+                    (0.55, 0.46),
+                    (0.5, 0.52),
+                    (0.5, 0.58),
+                ],
+                0.1,
+                Ink::Contrast,
+            ),
+            circle(0.5, 0.74, 0.06, Ink::Contrast),
+        ],
+        StampIcon::Cursor => {
+            let (tip, heel, notch, wing) = ((0.26, 0.08), (0.26, 0.82), (0.44, 0.66), (0.8, 0.61));
+            let tail = [(0.44, 0.66), (0.56, 0.92), (0.68, 0.87), (0.56, 0.61)];
+            let fill = |coords: &[(f32, f32)]| StampPart::Fill {
+                points: points(coords),
+                ink: Ink::Color,
+            };
+            vec![
+                fill(&[tip, heel, notch]),
+                fill(&[tip, notch, wing]),
+                fill(&tail),
+                StampPart::Outline {
+                    points: points(&[
+                        tip,
+                        heel,
+                        notch,
+                        (0.56, 0.92),
+                        (0.68, 0.87),
+                        (0.56, 0.61),
+                        wing,
+                    ]),
+                    width: 0.05 * side,
+                    ink: Ink::Contrast,
+                },
+            ]
+        }
+        StampIcon::ThumbsUp | StampIcon::ThumbsDown => {
+            let fist = Rect::from_two_pos(at(0.34, 0.44), at(0.86, 0.9));
+            vec![
+                StampPart::Fill {
+                    points: points(&[(0.1, 0.46), (0.27, 0.46), (0.27, 0.9), (0.1, 0.9)]),
+                    ink: Ink::Color,
+                },
+                StampPart::Fill {
+                    points: rounded_rect_outline(fist, 0.1 * side),
+                    ink: Ink::Color,
+                },
+                stroke(&[(0.42, 0.5), (0.52, 0.3), (0.55, 0.14)], 0.15, Ink::Color),
+                stroke(&[(0.62, 0.6), (0.8, 0.6)], 0.035, Ink::Contrast),
+                stroke(&[(0.62, 0.74), (0.8, 0.74)], 0.035, Ink::Contrast),
+            ]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, Rect};
+
+    use super::{stamp_parts, StampPart};
+    use crate::annotation::StampIcon;
+
+    #[test]
+    fn stamps_stay_square_inside_their_box() {
+        let rect = Rect::from_min_max(pos2(10.0, 20.0), pos2(110.0, 60.0));
+        let square = Rect::from_center_size(rect.center(), egui::Vec2::splat(40.0));
+        for icon in StampIcon::ALL {
+            for part in stamp_parts(icon, rect) {
+                let (points, reach) = match part {
+                    StampPart::Stroke { points, width, .. }
+                    | StampPart::Outline { points, width, .. } => (points, width * 0.5),
+                    StampPart::Fill { points, .. } => (points, 0.0),
+                    StampPart::Circle { center, radius, .. } => (
+                        vec![
+                            center - egui::vec2(radius, radius),
+                            center + egui::vec2(radius, radius),
+                        ],
+                        0.0,
+                    ),
+                };
+                for point in points {
+                    assert!(
+                        square.shrink(reach).expand(0.01).contains(point),
+                        "{icon:?} leaves its square at {point:?}"
+                    );
+                }
+            }
+        }
+
+        let up = stamp_parts(StampIcon::ThumbsUp, rect);
+        let down = stamp_parts(StampIcon::ThumbsDown, rect);
+        let (StampPart::Fill { points: up, .. }, StampPart::Fill { points: down, .. }) =
+            (&up[0], &down[0])
+        else {
+            panic!("thumbs start with the cuff");
+        };
+        assert_eq!(up[0].y + down[3].y, square.min.y + square.max.y);
+    }
+}
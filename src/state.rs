@@ -1,13 +1,22 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use anyhow::{Context, Result};
 use directories::ProjectDirs;
-use egui::{ColorImage, Context as EguiContext, Pos2, TextureHandle, TextureOptions, Vec2};
+use egui::{
+    ColorImage, Context as EguiContext, Pos2, Rangef, Rect, TextureHandle, TextureOptions, Vec2,
+};
 use image::DynamicImage;
 use serde::{Deserialize, Serialize};
 
-use crate::annotation::{Annotation, AnnotationId, Handle, Point, StrokeWidth, TextSize, Tool};
+use crate::annotation::{
+    default_line_spacing, default_spotlight_dim, Annotation, AnnotationId, AnnotationKind,
+    ArrowHead, GroupId, Handle, LoupeShape, Point, RectData, RedactMode, SpotlightShape, StampIcon,
+    StrokeStyle, StrokeWidth, TextAlign, TextEffects, TextFont, TextSize, Tool,
+};
 use crate::history::UndoHistory;
+use crate::redaction::PixelRegion;
+use crate::text_layout::TextLayout;
 
 pub const ZOOM_STEPS: &[f32] = &[0.25, 0.33, 0.5, 0.67, 0.75, 1.0, 1.5, 2.0, 3.0, 4.0];
 
@@ -68,6 +77,33 @@ pub struct EditorImage {
     pub dynamic: DynamicImage,
     pub texture: Option<TextureHandle>,
     pub scale_factor: f32,
+    pub redaction_previews: HashMap<AnnotationId, RedactionPreview>,
+    pub spotlight_gray: Option<SpotlightGray>,
+    pub text_halos: HashMap<AnnotationId, TextHalo>,
+}
+
+pub struct RedactionPreview {
+    pub rect: RectData,
+    pub mode: RedactMode,
+    pub color: [u8; 4],
+    pub region: PixelRegion,
+    pub texture: TextureHandle,
+}
+
+pub struct SpotlightGray {
+    pub redactions: Vec<(RectData, RedactMode, [u8; 4])>,
+    pub texture: TextureHandle,
+}
+
+pub struct TextHalo {
+    pub layout: TextLayout,
+    pub font: TextFont,
+    pub size: TextSize,
+    pub outline_width: f32,
+    pub scale: f32,
+    pub left: i32,
+    pub top: i32,
+    pub texture: TextureHandle,
 }
 
 impl EditorImage {
@@ -92,6 +128,31 @@ pub enum DragMode {
     Draw,
     Move,
     Resize,
+    Marquee,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZOrder {
+    BringForward,
+    SendBackward,
+    BringToFront,
+    SendToBack,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlignEdge {
+    Left,
+    Center,
+    Right,
+    Top,
+    Middle,
+    Bottom,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Distribute {
+    Horizontally,
+    Vertically,
 }
 
 #[derive(Clone, Debug)]
@@ -101,7 +162,8 @@ pub struct DragState {
     pub current: Point,
     pub selection_id: Option<AnnotationId>,
     pub handle: Option<Handle>,
-    pub original: Option<Annotation>,
+    pub originals: Vec<Annotation>,
+    pub points: Vec<Point>,
 }
 
 #[derive(Clone, Debug)]
@@ -112,6 +174,12 @@ pub enum TextEditTarget {
     NewArrowText {
         from: Point,
         to: Point,
+        head: ArrowHead,
+        color: [u8; 4],
+        stroke: StrokeWidth,
+    },
+    NewCallout {
+        rect: RectData,
         color: [u8; 4],
         stroke: StrokeWidth,
     },
@@ -132,8 +200,25 @@ pub struct TextEditState {
 #[serde(default)]
 pub struct UserSettings {
     pub last_color: [u8; 4],
+    pub last_highlight_color: [u8; 4],
     pub last_stroke: StrokeWidth,
+    pub last_stroke_style: StrokeStyle,
     pub last_text_size: TextSize,
+    pub last_text_effects: TextEffects,
+    pub last_text_font: TextFont,
+    pub last_text_align: TextAlign,
+    pub last_line_spacing: f32,
+    pub last_redact_mode: RedactMode,
+    pub last_arrow_head: ArrowHead,
+    pub last_polygon_fill: bool,
+    pub last_spotlight_shape: SpotlightShape,
+    pub last_spotlight_dim: f32,
+    pub last_spotlight_desaturate: bool,
+    pub last_loupe_shape: LoupeShape,
+    pub last_loupe_connector: bool,
+    pub last_stamp: StampIcon,
+    pub last_fill_enabled: bool,
+    pub last_fill_color: [u8; 4],
     pub dock_icon_visible: bool,
 }
 
@@ -141,8 +226,25 @@ impl Default for UserSettings {
     fn default() -> Self {
         Self {
             last_color: [229, 62, 62, 255],
-            last_stroke: StrokeWidth::Medium,
+            last_highlight_color: [255, 226, 61, 255],
+            last_stroke: StrokeWidth::MEDIUM,
+            last_stroke_style: StrokeStyle::Solid,
             last_text_size: TextSize::M,
+            last_text_effects: TextEffects::default(),
+            last_text_font: TextFont::default(),
+            last_text_align: TextAlign::Left,
+            last_line_spacing: default_line_spacing(),
+            last_redact_mode: RedactMode::Blur,
+            last_arrow_head: ArrowHead::Filled,
+            last_polygon_fill: false,
+            last_spotlight_shape: SpotlightShape::Rectangle,
+            last_spotlight_dim: default_spotlight_dim(),
+            last_spotlight_desaturate: false,
+            last_loupe_shape: LoupeShape::Circle,
+            last_loupe_connector: true,
+            last_stamp: StampIcon::Check,
+            last_fill_enabled: false,
+            last_fill_color: [229, 62, 62, 64],
             dock_icon_visible: true,
         }
     }
@@ -154,9 +256,28 @@ pub struct EditorState {
     pub history: UndoHistory<Vec<Annotation>>,
     pub active_tool: Tool,
     pub active_color: [u8; 4],
+    pub active_highlight_color: [u8; 4],
     pub active_stroke: StrokeWidth,
+    pub stroke_change_pending: bool,
+    pub active_stroke_style: StrokeStyle,
     pub active_text_size: TextSize,
-    pub selection: Option<AnnotationId>,
+    pub active_text_effects: TextEffects,
+    pub active_text_font: TextFont,
+    pub active_text_align: TextAlign,
+    pub active_line_spacing: f32,
+    pub active_redact_mode: RedactMode,
+    pub active_arrow_head: ArrowHead,
+    pub active_polygon_fill: bool,
+    pub active_spotlight_shape: SpotlightShape,
+    pub active_spotlight_dim: f32,
+    pub active_spotlight_desaturate: bool,
+    pub active_loupe_shape: LoupeShape,
+    pub active_loupe_connector: bool,
+    pub active_stamp: StampIcon,
+    pub active_fill_enabled: bool,
+    pub active_fill_color: [u8; 4],
+    pub polygon_draft: Vec<Point>,
+    pub selection: Vec<AnnotationId>,
     pub drag_state: Option<DragState>,
     pub text_edit: Option<TextEditState>,
     pub zoom: f32,
@@ -183,9 +304,28 @@ impl Default for EditorState {
             history: UndoHistory::new(Vec::new()),
             active_tool: Tool::Select,
             active_color: settings.last_color,
+            active_highlight_color: settings.last_highlight_color,
             active_stroke: settings.last_stroke,
+            stroke_change_pending: false,
+            active_stroke_style: settings.last_stroke_style,
             active_text_size: settings.last_text_size,
-            selection: None,
+            active_text_effects: settings.last_text_effects,
+            active_text_font: settings.last_text_font.clone(),
+            active_text_align: settings.last_text_align,
+            active_line_spacing: settings.last_line_spacing,
+            active_redact_mode: settings.last_redact_mode,
+            active_arrow_head: settings.last_arrow_head,
+            active_polygon_fill: settings.last_polygon_fill,
+            active_spotlight_shape: settings.last_spotlight_shape,
+            active_spotlight_dim: settings.last_spotlight_dim,
+            active_spotlight_desaturate: settings.last_spotlight_desaturate,
+            active_loupe_shape: settings.last_loupe_shape,
+            active_loupe_connector: settings.last_loupe_connector,
+            active_stamp: settings.last_stamp,
+            active_fill_enabled: settings.last_fill_enabled,
+            active_fill_color: settings.last_fill_color,
+            polygon_draft: Vec::new(),
+            selection: Vec::new(),
             drag_state: None,
             text_edit: None,
             zoom: 1.0,
@@ -226,7 +366,7 @@ impl EditorState {
     pub fn undo(&mut self) {
         if let Some(snapshot) = self.history.undo() {
             self.annotations = snapshot;
-            self.selection = None;
+            self.selection.clear();
             self.text_edit = None;
         }
     }
@@ -234,7 +374,7 @@ impl EditorState {
     pub fn redo(&mut self) {
         if let Some(snapshot) = self.history.redo() {
             self.annotations = snapshot;
-            self.selection = None;
+            self.selection.clear();
             self.text_edit = None;
         }
     }
@@ -249,14 +389,18 @@ impl EditorState {
             dynamic: image,
             texture: None,
             scale_factor,
+            redaction_previews: HashMap::new(),
+            spotlight_gray: None,
+            text_halos: HashMap::new(),
         });
         if let Some(editor_image) = self.image.as_mut() {
             editor_image.ensure_texture(ctx);
         }
         self.annotations.clear();
-        self.selection = None;
+        self.selection.clear();
         self.text_edit = None;
         self.drag_state = None;
+        self.polygon_draft.clear();
         self.has_edited = false;
         self.exported = false;
         self.zoom = 1.0;
@@ -276,6 +420,9 @@ impl EditorState {
         if tool == Tool::Select {
             self.drag_state = None;
         }
+        if tool != Tool::Polygon {
+            self.polygon_draft.clear();
+        }
     }
 
     pub fn set_color(&mut self, rgba: [u8; 4]) {
@@ -284,7 +431,7 @@ impl EditorState {
         let _ = self.settings.save();
 
         let mut changed_selection = false;
-        if let Some(selected_id) = self.selection {
+        for selected_id in self.selection.clone() {
             if let Some(annotation) = self
                 .annotations
                 .iter_mut()
@@ -302,13 +449,50 @@ impl EditorState {
         }
     }
 
-    pub fn set_stroke(&mut self, stroke: StrokeWidth) {
-        self.active_stroke = stroke;
-        self.settings.last_stroke = stroke;
+    pub fn set_highlight_color(&mut self, rgba: [u8; 4]) {
+        self.active_highlight_color = rgba;
+        self.settings.last_highlight_color = rgba;
         let _ = self.settings.save();
 
         let mut changed_selection = false;
-        if let Some(selected_id) = self.selection {
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.annotations.iter_mut().find(|annotation| {
+                annotation.id == selected_id
+                    && matches!(annotation.kind, AnnotationKind::Highlighter { .. })
+            }) {
+                if annotation.color != rgba {
+                    annotation.color = rgba;
+                    changed_selection = true;
+                }
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn uses_highlight_palette(&self) -> bool {
+        if self.active_tool == Tool::Highlighter {
+            return true;
+        }
+        self.selection.iter().any(|&selected_id| {
+            self.annotations.iter().any(|annotation| {
+                annotation.id == selected_id
+                    && matches!(annotation.kind, AnnotationKind::Highlighter { .. })
+            })
+        })
+    }
+
+    pub fn set_stroke(&mut self, stroke: StrokeWidth) {
+        self.preview_stroke(stroke);
+        self.commit_stroke();
+    }
+
+    pub fn preview_stroke(&mut self, stroke: StrokeWidth) {
+        self.active_stroke = stroke;
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
             if let Some(annotation) = self
                 .annotations
                 .iter_mut()
@@ -320,12 +504,278 @@ impl EditorState {
                 }
             }
         }
+        if changed_selection {
+            self.mark_changed();
+            self.stroke_change_pending = true;
+        }
+    }
+
+    pub fn commit_stroke(&mut self) {
+        self.settings.last_stroke = self.active_stroke;
+        let _ = self.settings.save();
+        if std::mem::take(&mut self.stroke_change_pending) {
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn set_stroke_style(&mut self, style: StrokeStyle) {
+        self.active_stroke_style = style;
+        self.settings.last_stroke_style = style;
+        let _ = self.settings.save();
+
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self
+                .annotations
+                .iter_mut()
+                .find(|annotation| annotation.id == selected_id)
+            {
+                if annotation.supports_stroke_style() && annotation.stroke_style != style {
+                    annotation.stroke_style = style;
+                    changed_selection = true;
+                }
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn uses_stroke_style_controls(&self) -> bool {
+        if matches!(
+            self.active_tool,
+            Tool::Arrow
+                | Tool::ArrowWithText
+                | Tool::Rectangle
+                | Tool::Ellipse
+                | Tool::Polygon
+                | Tool::Freehand
+        ) {
+            return true;
+        }
+        self.selection.iter().any(|&selected_id| {
+            self.annotations.iter().any(|annotation| {
+                annotation.id == selected_id && annotation.supports_stroke_style()
+            })
+        })
+    }
+
+    pub fn set_redact_mode(&mut self, mode: RedactMode) {
+        self.active_redact_mode = mode;
+        self.settings.last_redact_mode = mode;
+        let _ = self.settings.save();
+
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                if let AnnotationKind::Redact { mode: current, .. } = &mut annotation.kind {
+                    if *current != mode {
+                        *current = mode;
+                        changed_selection = true;
+                    }
+                }
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn uses_redact_controls(&self) -> bool {
+        if self.active_tool == Tool::Redact {
+            return true;
+        }
+        self.selection.iter().any(|&selected_id| {
+            self.annotations.iter().any(|annotation| {
+                annotation.id == selected_id
+                    && matches!(annotation.kind, AnnotationKind::Redact { .. })
+            })
+        })
+    }
+
+    pub fn set_arrow_head(&mut self, head: ArrowHead) {
+        self.active_arrow_head = head;
+        self.settings.last_arrow_head = head;
+        let _ = self.settings.save();
+
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                if let AnnotationKind::Arrow { head: current, .. }
+                | AnnotationKind::ArrowWithText { head: current, .. } = &mut annotation.kind
+                {
+                    if *current != head {
+                        *current = head;
+                        changed_selection = true;
+                    }
+                }
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn uses_arrow_head_controls(&self) -> bool {
+        if matches!(self.active_tool, Tool::Arrow | Tool::ArrowWithText) {
+            return true;
+        }
+        self.selection.iter().any(|&selected_id| {
+            self.annotations.iter().any(|annotation| {
+                annotation.id == selected_id
+                    && matches!(
+                        annotation.kind,
+                        AnnotationKind::Arrow { .. } | AnnotationKind::ArrowWithText { .. }
+                    )
+            })
+        })
+    }
+
+    pub fn set_text_effects(&mut self, effects: TextEffects) {
+        self.active_text_effects = effects;
+        self.settings.last_text_effects = effects;
+        let _ = self.settings.save();
+
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                if let AnnotationKind::Text {
+                    effects: current, ..
+                }
+                | AnnotationKind::ArrowWithText {
+                    effects: current, ..
+                } = &mut annotation.kind
+                {
+                    if *current != effects {
+                        *current = effects;
+                        changed_selection = true;
+                    }
+                }
+            }
+        }
         if changed_selection {
             self.mark_changed();
             self.push_history_snapshot();
         }
     }
 
+    pub fn set_text_font(&mut self, font: TextFont) {
+        self.active_text_font = font.clone();
+        self.settings.last_text_font = font.clone();
+        let _ = self.settings.save();
+        self.restyle_selected_text(|kind| match kind {
+            AnnotationKind::Text { font: current, .. }
+            | AnnotationKind::ArrowWithText { font: current, .. }
+                if *current != font =>
+            {
+                *current = font.clone();
+                true
+            }
+            _ => false,
+        });
+    }
+
+    pub fn set_text_align(&mut self, align: TextAlign) {
+        self.active_text_align = align;
+        self.settings.last_text_align = align;
+        let _ = self.settings.save();
+        self.restyle_selected_text(|kind| match kind {
+            AnnotationKind::Text { align: current, .. } if *current != align => {
+                *current = align;
+                true
+            }
+            _ => false,
+        });
+    }
+
+    pub fn set_line_spacing(&mut self, spacing: f32) {
+        self.active_line_spacing = spacing;
+        self.settings.last_line_spacing = spacing;
+        let _ = self.settings.save();
+        self.restyle_selected_text(|kind| match kind {
+            AnnotationKind::Text {
+                line_spacing: current,
+                ..
+            } if *current != spacing => {
+                *current = spacing;
+                true
+            }
+            _ => false,
+        });
+    }
+
+    pub fn set_text_auto_width(&mut self, auto: bool) {
+        let widths: HashMap<AnnotationId, f32> = self
+            .annotations
+            .iter()
+            .filter(|annotation| self.is_selected(annotation.id))
+            .filter_map(|annotation| Some((annotation.id, annotation.text_box()?.width())))
+            .collect();
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                if let AnnotationKind::Text { wrap_width, .. } = &mut annotation.kind {
+                    if wrap_width.is_none() != auto {
+                        *wrap_width = if auto {
+                            None
+                        } else {
+                            widths.get(&selected_id).copied()
+                        };
+                        changed_selection = true;
+                    }
+                }
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn selected_text_auto_width(&self) -> Option<bool> {
+        self.selection.iter().find_map(|&selected_id| {
+            match &self.annotations.iter().find(|a| a.id == selected_id)?.kind {
+                AnnotationKind::Text { wrap_width, .. } => Some(wrap_width.is_none()),
+                _ => None,
+            }
+        })
+    }
+
+    pub fn uses_text_layout_controls(&self) -> bool {
+        self.active_tool == Tool::Text || self.selected_text_auto_width().is_some()
+    }
+
+    fn restyle_selected_text(&mut self, mut apply: impl FnMut(&mut AnnotationKind) -> bool) {
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                changed_selection |= apply(&mut annotation.kind);
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn uses_text_effects_controls(&self) -> bool {
+        if matches!(self.active_tool, Tool::Text | Tool::ArrowWithText) {
+            return true;
+        }
+        self.selection.iter().any(|&selected_id| {
+            self.annotations.iter().any(|annotation| {
+                annotation.id == selected_id
+                    && matches!(
+                        annotation.kind,
+                        AnnotationKind::Text { .. } | AnnotationKind::ArrowWithText { .. }
+                    )
+            })
+        })
+    }
+
     pub fn set_text_size(&mut self, size: TextSize) {
         self.active_text_size = size;
         self.settings.last_text_size = size;
@@ -340,21 +790,615 @@ impl EditorState {
         let _ = self.settings.save();
     }
 
-    pub fn add_annotation(&mut self, annotation: Annotation) {
+    pub fn add_annotation(&mut self, mut annotation: Annotation) {
+        // Kinds that are never dashed don't keep the active dash style.
+        if !annotation.supports_stroke_style() {
+            annotation.stroke_style = StrokeStyle::Solid;
+        }
+        // Spotlights share one image-wide style, which the newest one sets.
+        if let AnnotationKind::Spotlight {
+            dim, desaturate, ..
+        } = annotation.kind
+        {
+            for other in &mut self.annotations {
+                if let AnnotationKind::Spotlight {
+                    dim: other_dim,
+                    desaturate: other_desaturate,
+                    ..
+                } = &mut other.kind
+                {
+                    *other_dim = dim;
+                    *other_desaturate = desaturate;
+                }
+            }
+        }
         self.annotations.push(annotation);
         self.mark_changed();
         self.push_history_snapshot();
     }
 
+    pub fn set_polygon_fill(&mut self, filled: bool) {
+        self.active_polygon_fill = filled;
+        self.settings.last_polygon_fill = filled;
+        let _ = self.settings.save();
+
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                if let AnnotationKind::Polygon {
+                    filled: current, ..
+                } = &mut annotation.kind
+                {
+                    if *current != filled {
+                        *current = filled;
+                        changed_selection = true;
+                    }
+                }
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn uses_polygon_fill_controls(&self) -> bool {
+        if self.active_tool == Tool::Polygon {
+            return true;
+        }
+        self.selection.iter().any(|&selected_id| {
+            self.annotations.iter().any(|annotation| {
+                annotation.id == selected_id
+                    && matches!(annotation.kind, AnnotationKind::Polygon { .. })
+            })
+        })
+    }
+
+    pub fn set_spotlight_shape(&mut self, shape: SpotlightShape) {
+        self.active_spotlight_shape = shape;
+        self.settings.last_spotlight_shape = shape;
+        let _ = self.settings.save();
+
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                if let AnnotationKind::Spotlight { shape: current, .. } = &mut annotation.kind {
+                    if *current != shape {
+                        *current = shape;
+                        changed_selection = true;
+                    }
+                }
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn set_spotlight_dim(&mut self, dim: f32) {
+        let dim = dim.clamp(0.0, 1.0);
+        self.active_spotlight_dim = dim;
+        self.settings.last_spotlight_dim = dim;
+        let _ = self.settings.save();
+        self.restyle_spotlights(|current, _| *current = dim);
+    }
+
+    pub fn set_spotlight_desaturate(&mut self, desaturate: bool) {
+        self.active_spotlight_desaturate = desaturate;
+        self.settings.last_spotlight_desaturate = desaturate;
+        let _ = self.settings.save();
+        self.restyle_spotlights(|_, current| *current = desaturate);
+    }
+
+    fn restyle_spotlights(&mut self, mut apply: impl FnMut(&mut f32, &mut bool)) {
+        let mut changed = false;
+        for annotation in &mut self.annotations {
+            if let AnnotationKind::Spotlight {
+                dim, desaturate, ..
+            } = &mut annotation.kind
+            {
+                let before = (*dim, *desaturate);
+                apply(dim, desaturate);
+                changed |= before != (*dim, *desaturate);
+            }
+        }
+        if changed {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn uses_spotlight_controls(&self) -> bool {
+        if self.active_tool == Tool::Spotlight {
+            return true;
+        }
+        self.selection.iter().any(|&selected_id| {
+            self.annotations.iter().any(|annotation| {
+                annotation.id == selected_id
+                    && matches!(annotation.kind, AnnotationKind::Spotlight { .. })
+            })
+        })
+    }
+
+    pub fn set_loupe_shape(&mut self, shape: LoupeShape) {
+        self.active_loupe_shape = shape;
+        self.settings.last_loupe_shape = shape;
+        let _ = self.settings.save();
+
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                if let AnnotationKind::Magnifier {
+                    target,
+                    shape: current,
+                    ..
+                } = &mut annotation.kind
+                {
+                    if *current != shape {
+                        *current = shape;
+                        // A circular loupe is always round.
+                        if shape == LoupeShape::Circle {
+                            let corner = target.normalize().max;
+                            target.resize_from_handle(Handle::BottomRight, corner, true);
+                        }
+                        changed_selection = true;
+                    }
+                }
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn set_loupe_connector(&mut self, connector: bool) {
+        self.active_loupe_connector = connector;
+        self.settings.last_loupe_connector = connector;
+        let _ = self.settings.save();
+
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                if let AnnotationKind::Magnifier {
+                    connector: current, ..
+                } = &mut annotation.kind
+                {
+                    if *current != connector {
+                        *current = connector;
+                        changed_selection = true;
+                    }
+                }
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn uses_loupe_controls(&self) -> bool {
+        if self.active_tool == Tool::Magnifier {
+            return true;
+        }
+        self.selection.iter().any(|&selected_id| {
+            self.annotations.iter().any(|annotation| {
+                annotation.id == selected_id
+                    && matches!(annotation.kind, AnnotationKind::Magnifier { .. })
+            })
+        })
+    }
+
+    pub fn set_stamp_icon(&mut self, icon: StampIcon) {
+        self.active_stamp = icon;
+        self.settings.last_stamp = icon;
+        let _ = self.settings.save();
+
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                if let AnnotationKind::Stamp { icon: current, .. } = &mut annotation.kind {
+                    if *current != icon {
+                        *current = icon;
+                        changed_selection = true;
+                    }
+                }
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn uses_stamp_controls(&self) -> bool {
+        if self.active_tool == Tool::Stamp {
+            return true;
+        }
+        self.selection.iter().any(|&selected_id| {
+            self.annotations.iter().any(|annotation| {
+                annotation.id == selected_id
+                    && matches!(annotation.kind, AnnotationKind::Stamp { .. })
+            })
+        })
+    }
+
+    pub fn active_fill(&self) -> Option<[u8; 4]> {
+        self.active_fill_enabled.then_some(self.active_fill_color)
+    }
+
+    pub fn set_fill_enabled(&mut self, enabled: bool) {
+        self.active_fill_enabled = enabled;
+        self.settings.last_fill_enabled = enabled;
+        let _ = self.settings.save();
+        self.apply_fill_to_selection();
+    }
+
+    pub fn set_fill_color(&mut self, rgba: [u8; 4]) {
+        self.active_fill_color = [rgba[0], rgba[1], rgba[2], self.active_fill_color[3]];
+        self.active_fill_enabled = true;
+        self.settings.last_fill_color = self.active_fill_color;
+        self.settings.last_fill_enabled = true;
+        let _ = self.settings.save();
+        self.apply_fill_to_selection();
+    }
+
+    pub fn set_fill_opacity(&mut self, alpha: u8) {
+        self.active_fill_color[3] = alpha;
+        self.settings.last_fill_color = self.active_fill_color;
+        let _ = self.settings.save();
+        self.apply_fill_to_selection();
+    }
+
+    fn apply_fill_to_selection(&mut self) {
+        let fill = self.active_fill();
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                if let AnnotationKind::Rectangle { fill: current, .. }
+                | AnnotationKind::Ellipse { fill: current, .. } = &mut annotation.kind
+                {
+                    if *current != fill {
+                        *current = fill;
+                        changed_selection = true;
+                    }
+                }
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn uses_fill_controls(&self) -> bool {
+        if matches!(self.active_tool, Tool::Rectangle | Tool::Ellipse) {
+            return true;
+        }
+        self.selection.iter().any(|&selected_id| {
+            self.annotations.iter().any(|annotation| {
+                annotation.id == selected_id
+                    && matches!(
+                        annotation.kind,
+                        AnnotationKind::Rectangle { .. } | AnnotationKind::Ellipse { .. }
+                    )
+            })
+        })
+    }
+
+    pub fn finish_polygon(&mut self, closed: bool) {
+        let points = std::mem::take(&mut self.polygon_draft);
+        if points.len() < 2 {
+            return;
+        }
+        let closed = closed && points.len() >= 3;
+        let id = self.next_annotation_id();
+        self.add_annotation(Annotation {
+            id,
+            kind: AnnotationKind::Polygon {
+                points,
+                closed,
+                filled: self.active_polygon_fill,
+            },
+            color: self.active_color,
+            stroke_width: self.active_stroke,
+            stroke_style: self.active_stroke_style,
+            rotation: 0.0,
+            group: None,
+        });
+        self.set_tool(Tool::Select);
+    }
+
     pub fn delete_selected(&mut self) {
-        if let Some(selected) = self.selection.take() {
-            self.annotations
-                .retain(|annotation| annotation.id != selected);
+        if self.selection.is_empty() {
+            return;
+        }
+        let selected = std::mem::take(&mut self.selection);
+        self.annotations
+            .retain(|annotation| !selected.contains(&annotation.id));
+        self.renumber_steps();
+        self.mark_changed();
+        self.push_history_snapshot();
+    }
+
+    pub fn reorder_selected(&mut self, order: ZOrder) {
+        let before: Vec<_> = self.annotations.iter().map(|item| item.id).collect();
+        let selection = self.selection.clone();
+        let selected = |annotation: &Annotation| selection.contains(&annotation.id);
+        match order {
+            ZOrder::BringToFront => self.annotations.sort_by_key(selected),
+            ZOrder::SendToBack => self.annotations.sort_by_key(|item| !selected(item)),
+            ZOrder::BringForward => {
+                for index in (0..self.annotations.len().saturating_sub(1)).rev() {
+                    if selected(&self.annotations[index]) && !selected(&self.annotations[index + 1])
+                    {
+                        self.annotations.swap(index, index + 1);
+                    }
+                }
+            }
+            ZOrder::SendBackward => {
+                for index in 1..self.annotations.len() {
+                    if selected(&self.annotations[index]) && !selected(&self.annotations[index - 1])
+                    {
+                        self.annotations.swap(index, index - 1);
+                    }
+                }
+            }
+        }
+        if self.annotations.iter().map(|item| item.id).ne(before) {
             self.mark_changed();
             self.push_history_snapshot();
         }
     }
 
+    pub fn selected_units(&self) -> Vec<(Vec<AnnotationId>, Rect)> {
+        let mut units: Vec<(Option<GroupId>, Vec<AnnotationId>, Rect)> = Vec::new();
+        for annotation in &self.annotations {
+            if !self.is_selected(annotation.id) {
+                continue;
+            }
+            let bounds = annotation.bounds();
+            let unit = annotation.group.and_then(|group| {
+                units
+                    .iter_mut()
+                    .find(|(unit_group, _, _)| *unit_group == Some(group))
+            });
+            match unit {
+                Some((_, ids, unit_bounds)) => {
+                    ids.push(annotation.id);
+                    *unit_bounds = unit_bounds.union(bounds);
+                }
+                None => units.push((annotation.group, vec![annotation.id], bounds)),
+            }
+        }
+        units
+            .into_iter()
+            .map(|(_, ids, bounds)| (ids, bounds))
+            .collect()
+    }
+
+    pub fn align_selected(&mut self, edge: AlignEdge) {
+        let units = self.selected_units();
+        let Some(all) = units.iter().map(|(_, bounds)| *bounds).reduce(Rect::union) else {
+            return;
+        };
+        if units.len() < 2 {
+            return;
+        }
+        let moves = units
+            .into_iter()
+            .map(|(ids, bounds)| {
+                let delta = match edge {
+                    AlignEdge::Left => Vec2::new(all.left() - bounds.left(), 0.0),
+                    AlignEdge::Center => Vec2::new(all.center().x - bounds.center().x, 0.0),
+                    AlignEdge::Right => Vec2::new(all.right() - bounds.right(), 0.0),
+                    AlignEdge::Top => Vec2::new(0.0, all.top() - bounds.top()),
+                    AlignEdge::Middle => Vec2::new(0.0, all.center().y - bounds.center().y),
+                    AlignEdge::Bottom => Vec2::new(0.0, all.bottom() - bounds.bottom()),
+                };
+                (ids, delta)
+            })
+            .collect();
+        self.move_units(moves);
+    }
+
+    pub fn distribute_selected(&mut self, axis: Distribute) {
+        let mut units = self.selected_units();
+        if units.len() < 3 {
+            return;
+        }
+        let extent: fn(&Rect) -> Rangef = match axis {
+            Distribute::Horizontally => Rect::x_range,
+            Distribute::Vertically => Rect::y_range,
+        };
+        units.sort_by(|(_, a), (_, b)| extent(a).center().total_cmp(&extent(b).center()));
+        let first = extent(&units[0].1).min;
+        let last = units
+            .iter()
+            .map(|(_, bounds)| extent(bounds).max)
+            .fold(f32::MIN, f32::max);
+        let occupied: f32 = units.iter().map(|(_, bounds)| extent(bounds).span()).sum();
+        let gap = (last - first - occupied) / (units.len() - 1) as f32;
+
+        let mut cursor = first;
+        let moves = units
+            .into_iter()
+            .map(|(ids, bounds)| {
+                let shift = cursor - extent(&bounds).min;
+                cursor += extent(&bounds).span() + gap;
+                let delta = match axis {
+                    Distribute::Horizontally => Vec2::new(shift, 0.0),
+                    Distribute::Vertically => Vec2::new(0.0, shift),
+                };
+                (ids, delta)
+            })
+            .collect();
+        self.move_units(moves);
+    }
+
+    fn move_units(&mut self, moves: Vec<(Vec<AnnotationId>, Vec2)>) {
+        let mut moved = false;
+        for (ids, delta) in moves {
+            if delta.length_sq() <= f32::EPSILON {
+                continue;
+            }
+            moved = true;
+            for id in ids {
+                if let Some(annotation) = self.find_annotation_mut(id) {
+                    annotation.move_by(delta);
+                }
+            }
+        }
+        if moved {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn is_selected(&self, id: AnnotationId) -> bool {
+        self.selection.contains(&id)
+    }
+
+    pub fn select(&mut self, id: Option<AnnotationId>) {
+        self.selection.clear();
+        if let Some(id) = id {
+            self.add_to_selection(id);
+        }
+    }
+
+    pub fn add_to_selection(&mut self, id: AnnotationId) {
+        for member in self.group_members(id) {
+            if !self.selection.contains(&member) {
+                self.selection.push(member);
+            }
+        }
+    }
+
+    pub fn toggle_selected(&mut self, id: AnnotationId) {
+        if self.is_selected(id) {
+            let members = self.group_members(id);
+            self.selection
+                .retain(|selected| !members.contains(selected));
+        } else {
+            self.add_to_selection(id);
+        }
+    }
+
+    fn group_members(&self, id: AnnotationId) -> Vec<AnnotationId> {
+        let group = self
+            .annotations
+            .iter()
+            .find(|annotation| annotation.id == id)
+            .and_then(|annotation| annotation.group);
+        match group {
+            Some(group) => self
+                .annotations
+                .iter()
+                .filter(|annotation| annotation.group == Some(group))
+                .map(|annotation| annotation.id)
+                .collect(),
+            None => vec![id],
+        }
+    }
+
+    pub fn selected_group(&self) -> Option<GroupId> {
+        let mut groups = self
+            .annotations
+            .iter()
+            .filter(|annotation| self.is_selected(annotation.id))
+            .map(|annotation| annotation.group);
+        let group = groups.next()??;
+        if !groups.all(|other| other == Some(group)) {
+            return None;
+        }
+        let size = self
+            .annotations
+            .iter()
+            .filter(|annotation| annotation.group == Some(group))
+            .count();
+        (size == self.selection.len()).then_some(group)
+    }
+
+    pub fn group_bounds(&self, group: GroupId) -> Option<Rect> {
+        self.annotations
+            .iter()
+            .filter(|annotation| annotation.group == Some(group))
+            .map(Annotation::bounds)
+            .reduce(Rect::union)
+    }
+
+    pub fn group_selected(&mut self) {
+        if self.selection.len() < 2 || self.selected_group().is_some() {
+            return;
+        }
+        let group = self.next_annotation_id();
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                annotation.group = Some(group);
+            }
+        }
+        self.mark_changed();
+        self.push_history_snapshot();
+    }
+
+    pub fn ungroup_selected(&mut self) {
+        let mut changed_selection = false;
+        for selected_id in self.selection.clone() {
+            if let Some(annotation) = self.find_annotation_mut(selected_id) {
+                changed_selection |= annotation.group.take().is_some();
+            }
+        }
+        if changed_selection {
+            self.mark_changed();
+            self.push_history_snapshot();
+        }
+    }
+
+    pub fn select_all(&mut self) {
+        self.selection = self
+            .annotations
+            .iter()
+            .map(|annotation| annotation.id)
+            .collect();
+    }
+
+    pub fn single_selection(&self) -> Option<&Annotation> {
+        match self.selection.as_slice() {
+            [id] => self
+                .annotations
+                .iter()
+                .find(|annotation| annotation.id == *id),
+            _ => None,
+        }
+    }
+
+    pub fn next_step_number(&self) -> u32 {
+        self.annotations
+            .iter()
+            .filter(|annotation| matches!(annotation.kind, AnnotationKind::Step { .. }))
+            .count() as u32
+            + 1
+    }
+
+    fn renumber_steps(&mut self) {
+        let mut steps: Vec<(u32, usize)> = self
+            .annotations
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, annotation)| match annotation.kind {
+                AnnotationKind::Step { number, .. } => Some((number, idx)),
+                _ => None,
+            })
+            .collect();
+        steps.sort();
+        for (new_number, (_, idx)) in (1..).zip(steps) {
+            if let AnnotationKind::Step { number, .. } = &mut self.annotations[idx].kind {
+                *number = new_number;
+            }
+        }
+    }
+
     pub fn nearest_zoom_step(&self) -> usize {
         let mut best_idx = 0usize;
         let mut best_diff = f32::MAX;
@@ -410,8 +1454,298 @@ impl UserSettings {
     }
 
     pub fn save(&self) -> Result<()> {
+        // Tests exercise the setters without touching the real settings file.
+        if cfg!(test) {
+            return Ok(());
+        }
         let path = Self::file_path().context("cannot resolve settings path")?;
         std::fs::write(path, serde_json::to_string_pretty(self)?)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use egui::Vec2;
+
+    use super::{AlignEdge, Distribute, EditorState, ZOrder};
+    use crate::annotation::{
+        Annotation, AnnotationKind, Point, RectData, SpotlightShape, StrokeStyle, StrokeWidth,
+        TextSize,
+    };
+
+    fn add_step(state: &mut EditorState) -> u64 {
+        let id = state.next_annotation_id();
+        let number = state.next_step_number();
+        state.add_annotation(Annotation {
+            id,
+            kind: AnnotationKind::Step {
+                center: Point::new(10.0 * id as f32, 10.0),
+                number,
+                size: TextSize::M,
+            },
+            color: [229, 62, 62, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Solid,
+            rotation: 0.0,
+            group: None,
+        });
+        id
+    }
+
+    fn step_numbers(state: &EditorState) -> Vec<u32> {
+        state
+            .annotations
+            .iter()
+            .filter_map(|annotation| match annotation.kind {
+                AnnotationKind::Step { number, .. } => Some(number),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deleting_a_step_renumbers_and_undo_restores() {
+        let mut state = EditorState::default();
+        add_step(&mut state);
+        let second = add_step(&mut state);
+        add_step(&mut state);
+        assert_eq!(step_numbers(&state), vec![1, 2, 3]);
+
+        state.select(Some(second));
+        state.delete_selected();
+        assert_eq!(step_numbers(&state), vec![1, 2]);
+        assert_eq!(state.next_step_number(), 3);
+
+        state.undo();
+        assert_eq!(step_numbers(&state), vec![1, 2, 3]);
+        state.redo();
+        assert_eq!(step_numbers(&state), vec![1, 2]);
+    }
+
+    #[test]
+    fn spotlights_share_one_dim_and_desaturate_setting() {
+        let mut state = EditorState::default();
+        let add_spotlight = |state: &mut EditorState, dim: f32, desaturate: bool| {
+            let id = state.next_annotation_id();
+            state.add_annotation(Annotation {
+                id,
+                kind: AnnotationKind::Spotlight {
+                    rect: RectData {
+                        min: Point::new(0.0, 0.0),
+                        max: Point::new(10.0, 10.0),
+                    },
+                    shape: SpotlightShape::Rectangle,
+                    dim,
+                    desaturate,
+                },
+                color: [0, 0, 0, 255],
+                stroke_width: StrokeWidth::MEDIUM,
+                stroke_style: StrokeStyle::Solid,
+                rotation: 0.0,
+                group: None,
+            });
+        };
+        let styles = |state: &EditorState| -> Vec<(f32, bool)> {
+            state
+                .annotations
+                .iter()
+                .filter_map(|annotation| match annotation.kind {
+                    AnnotationKind::Spotlight {
+                        dim, desaturate, ..
+                    } => Some((dim, desaturate)),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        add_spotlight(&mut state, 0.8, true);
+        add_spotlight(&mut state, 0.3, false);
+        assert_eq!(styles(&state), vec![(0.3, false); 2]);
+
+        state.set_spotlight_dim(0.6);
+        assert_eq!(styles(&state), vec![(0.6, false); 2]);
+    }
+
+    #[test]
+    fn only_dashable_kinds_keep_a_dash_style() {
+        let mut state = EditorState::default();
+        let step = add_step(&mut state);
+        state.find_annotation_mut(step).unwrap().stroke_style = StrokeStyle::Dashed;
+        let badge = state.annotations.pop().unwrap();
+        state.add_annotation(badge);
+        let id = state.next_annotation_id();
+        state.add_annotation(Annotation {
+            id,
+            kind: AnnotationKind::Freehand {
+                points: vec![Point::new(0.0, 0.0), Point::new(10.0, 0.0)],
+            },
+            color: [0, 0, 0, 255],
+            stroke_width: StrokeWidth::MEDIUM,
+            stroke_style: StrokeStyle::Dotted,
+            rotation: 0.0,
+            group: None,
+        });
+
+        let styles: Vec<_> = state
+            .annotations
+            .iter()
+            .map(|annotation| annotation.stroke_style)
+            .collect();
+        assert_eq!(styles, vec![StrokeStyle::Solid, StrokeStyle::Dotted]);
+    }
+
+    #[test]
+    fn bulk_edits_on_a_selection_are_single_undo_steps() {
+        let mut state = EditorState::default();
+        let first = add_step(&mut state);
+        let second = add_step(&mut state);
+        let third = add_step(&mut state);
+
+        state.select(Some(first));
+        state.toggle_selected(third);
+        state.toggle_selected(first);
+        assert_eq!(state.selection, vec![third]);
+
+        state.select_all();
+        assert_eq!(state.selection, vec![first, second, third]);
+        state.set_color([0, 0, 255, 255]);
+        state.set_stroke(StrokeWidth::from_px(9.0));
+        assert!(state
+            .annotations
+            .iter()
+            .all(|annotation| annotation.color == [0, 0, 255, 255]
+                && annotation.stroke_width.px() == 9.0));
+
+        state.undo();
+        assert!(state
+            .annotations
+            .iter()
+            .all(|annotation| annotation.color == [0, 0, 255, 255]
+                && annotation.stroke_width == StrokeWidth::MEDIUM));
+        state.undo();
+        assert!(state
+            .annotations
+            .iter()
+            .all(|annotation| annotation.color == [229, 62, 62, 255]));
+
+        state.selection = vec![first, third];
+        state.delete_selected();
+        assert_eq!(step_numbers(&state), vec![1]);
+        state.undo();
+        assert_eq!(step_numbers(&state), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn groups_select_and_delete_as_one_and_survive_undo_and_serde() {
+        let mut state = EditorState::default();
+        let first = add_step(&mut state);
+        let second = add_step(&mut state);
+        let third = add_step(&mut state);
+
+        state.selection = vec![first, second];
+        state.group_selected();
+        state.select(Some(second));
+        assert_eq!(state.selection, vec![first, second]);
+        let group = state.selected_group().expect("whole group selected");
+        state.group_selected();
+        assert_eq!(state.selected_group(), Some(group));
+
+        let json = serde_json::to_string(&state.annotations).unwrap();
+        let restored: Vec<Annotation> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored[0].group, Some(group));
+        assert_eq!(restored[2].group, None);
+
+        state.ungroup_selected();
+        state.select(Some(first));
+        assert_eq!(state.selection, vec![first]);
+        state.undo();
+        state.select(Some(first));
+        assert_eq!(state.selected_group(), Some(group));
+        state.undo();
+        state.select(Some(first));
+        assert_eq!(state.selected_group(), None);
+        state.redo();
+        state.redo();
+        state.select(Some(first));
+        assert_eq!(state.selected_group(), None);
+
+        state.undo();
+        state.select(Some(third));
+        state.toggle_selected(first);
+        state.delete_selected();
+        assert!(state.annotations.is_empty());
+    }
+
+    #[test]
+    fn z_order_moves_the_selection_as_a_block() {
+        let mut state = EditorState::default();
+        let ids: Vec<_> = (0..4).map(|_| add_step(&mut state)).collect();
+        let order = |state: &EditorState| -> Vec<_> {
+            state.annotations.iter().map(|item| item.id).collect()
+        };
+
+        state.selection = vec![ids[0], ids[1]];
+        state.reorder_selected(ZOrder::BringForward);
+        assert_eq!(order(&state), vec![ids[2], ids[0], ids[1], ids[3]]);
+        state.reorder_selected(ZOrder::BringToFront);
+        assert_eq!(order(&state), vec![ids[2], ids[3], ids[0], ids[1]]);
+        state.reorder_selected(ZOrder::BringToFront);
+
+        state.selection = vec![ids[3]];
+        state.reorder_selected(ZOrder::SendBackward);
+        assert_eq!(order(&state), vec![ids[3], ids[2], ids[0], ids[1]]);
+        state.selection = vec![ids[1]];
+        state.reorder_selected(ZOrder::SendToBack);
+        assert_eq!(order(&state), vec![ids[1], ids[3], ids[2], ids[0]]);
+        assert_eq!(step_numbers(&state), vec![2, 4, 3, 1]);
+
+        state.undo();
+        state.undo();
+        assert_eq!(order(&state), vec![ids[2], ids[3], ids[0], ids[1]]);
+    }
+
+    #[test]
+    fn align_and_distribute_move_whole_pieces_in_one_step() {
+        let mut state = EditorState::default();
+        let ids: Vec<_> = (0..3).map(|_| add_step(&mut state)).collect();
+        state
+            .find_annotation_mut(ids[1])
+            .unwrap()
+            .move_by(Vec2::new(50.0, 40.0));
+        state
+            .find_annotation_mut(ids[2])
+            .unwrap()
+            .move_by(Vec2::new(0.0, 5.0));
+        state.push_history_snapshot();
+        let center = |state: &EditorState, id| {
+            state
+                .annotations
+                .iter()
+                .find(|annotation| annotation.id == id)
+                .unwrap()
+                .bounds()
+                .center()
+        };
+
+        state.select_all();
+        state.distribute_selected(Distribute::Horizontally);
+        assert_eq!(center(&state, ids[0]).x, 10.0);
+        assert_eq!(center(&state, ids[1]).x, 70.0);
+        assert_eq!(center(&state, ids[2]).x, 40.0);
+        state.undo();
+        assert_eq!(center(&state, ids[2]).x, 30.0);
+
+        state.select_all();
+        state.align_selected(AlignEdge::Top);
+        assert!(ids.iter().all(|&id| center(&state, id).y == 10.0));
+
+        state.selection = vec![ids[0], ids[1]];
+        state.group_selected();
+        state.select_all();
+        assert_eq!(state.selected_units().len(), 2);
+        state.align_selected(AlignEdge::Right);
+        assert_eq!(center(&state, ids[1]).x, center(&state, ids[2]).x);
+        assert_eq!(center(&state, ids[1]).x - center(&state, ids[0]).x, 60.0);
+    }
+}
@@ -0,0 +1,297 @@
+use ab_glyph::{point, Font, FontArc, Glyph, PxScale, ScaleFont};
+use egui::Vec2;
+use image::GrayImage;
+
+use crate::annotation::TextAlign;
+use crate::fonts::FontChain;
+
+/// One laid-out line, positioned relative to the top-left of the text box.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLine {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLayout {
+    pub lines: Vec<TextLine>,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// A glyph positioned on a line, with the face that draws it.
+pub struct PlacedGlyph {
+    pub ch: char,
+    pub font: &'static FontArc,
+    pub glyph: Glyph,
+}
+
+/// Glyphs of one line with its top-left corner at the origin, and the line's
+/// advance width. Each character comes from the first face in `chain` that
+/// has it, and all faces share the primary face's baseline.
+pub fn line_glyphs(chain: FontChain, size: f32, text: &str) -> (Vec<PlacedGlyph>, f32) {
+    let scale = PxScale::from(size);
+    let baseline = chain.primary().as_scaled(scale).ascent();
+    let mut glyphs = Vec::new();
+    let mut x = 0.0;
+    let mut previous: Option<&PlacedGlyph> = None;
+    for ch in text.chars() {
+        let font = chain.font_for(ch).unwrap_or(chain.primary());
+        let scaled = font.as_scaled(scale);
+        let id = scaled.glyph_id(ch);
+        if let Some(previous) = previous.filter(|previous| std::ptr::eq(previous.font, font)) {
+            x += scaled.kern(previous.glyph.id, id);
+        }
+        glyphs.push(PlacedGlyph {
+            ch,
+            font,
+            glyph: id.with_scale_and_position(scale, point(x, baseline)),
+        });
+        x += scaled.h_advance(id);
+        previous = glyphs.last();
+    }
+    (glyphs, x)
+}
+
+/// Advance width of a single line of text, including kerning.
+pub fn text_width(chain: FontChain, size: f32, text: &str) -> f32 {
+    line_glyphs(chain, size, text).1
+}
+
+/// Height of one line of text without extra spacing.
+pub fn line_height(chain: FontChain, size: f32) -> f32 {
+    let font = chain.primary().as_scaled(PxScale::from(size));
+    font.height() + font.line_gap()
+}
+
+/// Breaks `text` at newlines and, when `wrap_width` is set, greedily between
+/// words so no line is wider than the box. Words wider than the box are
+/// broken between characters. Lines are aligned within the box and spaced
+/// `line_spacing` times the font's line height apart.
+pub fn layout_text(
+    font: FontChain,
+    text: &str,
+    size: f32,
+    wrap_width: Option<f32>,
+    align: TextAlign,
+    line_spacing: f32,
+) -> TextLayout {
+    let lines = match wrap_width {
+        Some(max_width) => wrap_lines(font, size, text, max_width),
+        None => text.split('\n').map(str::to_string).collect(),
+    };
+    let natural = line_height(font, size);
+    let advance = natural * line_spacing.max(0.5);
+    let widths: Vec<f32> = lines
+        .iter()
+        .map(|line| text_width(font, size, line))
+        .collect();
+    let width = wrap_width.unwrap_or_else(|| widths.iter().copied().fold(0.0, f32::max));
+
+    let lines = lines
+        .into_iter()
+        .zip(widths)
+        .enumerate()
+        .map(|(index, (text, line_width))| {
+            let x = match align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => (width - line_width) * 0.5,
+                TextAlign::Right => width - line_width,
+            };
+            TextLine {
+                text,
+                x,
+                y: index as f32 * advance,
+                width: line_width,
+            }
+        })
+        .collect::<Vec<_>>();
+    let height = (lines.len().max(1) - 1) as f32 * advance + natural;
+    TextLayout {
+        lines,
+        width,
+        height,
+    }
+}
+
+fn wrap_lines(font: FontChain, size: f32, text: &str, max_width: f32) -> Vec<String> {
+    let fits = |candidate: &str| text_width(font, size, candidate) <= max_width;
+    // Only the spaces a line is broken at are dropped, so a line that fits
+    // keeps the same characters as without a wrap width.
+    let break_line = |lines: &mut Vec<String>, line: &mut String| {
+        line.truncate(line.trim_end().len());
+        lines.push(std::mem::take(line));
+    };
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for token in whitespace_runs(paragraph) {
+            let candidate = format!("{line}{token}");
+            if fits(&candidate) {
+                line = candidate;
+                continue;
+            }
+            if token.starts_with(char::is_whitespace) {
+                if !line.is_empty() {
+                    break_line(&mut lines, &mut line);
+                }
+                continue;
+            }
+            if !line.is_empty() {
+                break_line(&mut lines, &mut line);
+            }
+            for ch in token.chars() {
+                line.push(ch);
+                if !fits(&line) && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::take(&mut line));
+                    line.push(ch);
+                }
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Splits `text` into alternating words and runs of whitespace.
+fn whitespace_runs(text: &str) -> Vec<&str> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut in_space = None;
+    for (index, ch) in text.char_indices() {
+        let space = ch.is_whitespace();
+        if in_space.is_some_and(|previous| previous != space) {
+            runs.push(&text[start..index]);
+            start = index;
+        }
+        in_space = Some(space);
+    }
+    if start < text.len() {
+        runs.push(&text[start..]);
+    }
+    runs
+}
+
+/// A text layout placed on a pixel grid: `x`/`y` is its top-left corner in
+/// pixels and `scale` maps the layout's image pixels onto them.
+pub struct TextStamp<'a> {
+    pub font: FontChain,
+    pub font_size: f32,
+    pub layout: &'a TextLayout,
+    pub x: f32,
+    pub y: f32,
+    pub scale: f32,
+    /// Longest offset any stamp is shifted by.
+    pub reach: f32,
+}
+
+impl TextStamp<'_> {
+    /// Rasterises the layout once per offset into one coverage mask, keeping
+    /// the strongest coverage where stamps overlap. Returns the mask with the
+    /// pixel position of its top-left corner.
+    pub fn coverage(&self, offsets: &[Vec2]) -> (GrayImage, i32, i32) {
+        let TextStamp {
+            font,
+            font_size,
+            layout,
+            x,
+            y,
+            scale,
+            reach,
+        } = *self;
+        // Italic and fallback glyphs can overhang their line box a little.
+        let margin = (reach + font_size * 0.5).ceil() as u32 + 1;
+        let (left, top) = (
+            x.floor() as i32 - margin as i32,
+            y.floor() as i32 - margin as i32,
+        );
+        let mut mask = GrayImage::new(
+            (layout.width * scale).ceil() as u32 + margin * 2 + 1,
+            (layout.height * scale).ceil() as u32 + margin * 2 + 1,
+        );
+        let origin = Vec2::new(x - left as f32, y - top as f32);
+        for offset in offsets {
+            for line in &layout.lines {
+                let (glyphs, _) = line_glyphs(font, font_size, &line.text);
+                for placed in glyphs {
+                    let mut glyph = placed.glyph;
+                    glyph.position.x += origin.x + line.x * scale + offset.x;
+                    glyph.position.y += origin.y + line.y * scale + offset.y;
+                    let Some(outlined) = placed.font.outline_glyph(glyph) else {
+                        continue;
+                    };
+                    let bounds = outlined.px_bounds();
+                    outlined.draw(|gx, gy, coverage| {
+                        let px = bounds.min.x as i32 + gx as i32;
+                        let py = bounds.min.y as i32 + gy as i32;
+                        if px < 0
+                            || py < 0
+                            || px >= mask.width() as i32
+                            || py >= mask.height() as i32
+                        {
+                            return;
+                        }
+                        let value = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+                        let pixel = mask.get_pixel_mut(px as u32, py as u32);
+                        pixel.0[0] = pixel.0[0].max(value);
+                    });
+                }
+            }
+        }
+        (mask, left, top)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{layout_text, line_height, text_width};
+    use crate::annotation::{TextAlign, TextFont};
+    use crate::fonts::font_chain;
+
+    #[test]
+    fn wrapped_lines_fit_the_box_and_align_within_it() {
+        let font = font_chain(&TextFont::default());
+        let text = "the quick brown fox jumps over the lazy dog\nend";
+        let layout = layout_text(font, text, 18.0, Some(120.0), TextAlign::Right, 1.5);
+
+        assert!(layout.lines.len() > 2);
+        assert_eq!(
+            layout.lines.last().map(|line| line.text.as_str()),
+            Some("end")
+        );
+        for line in &layout.lines {
+            assert!(line.width <= 120.0, "{:?} overflows", line.text);
+            assert!((line.x + line.width - 120.0).abs() < 0.01);
+        }
+        let advance = line_height(font, 18.0) * 1.5;
+        assert!((layout.lines[1].y - advance).abs() < 0.01);
+
+        let unwrapped = layout_text(font, "wide", 18.0, None, TextAlign::Left, 1.0);
+        assert_eq!(unwrapped.width, text_width(font, 18.0, "wide"));
+    }
+
+    #[test]
+    fn wrapping_keeps_indentation_and_space_runs() {
+        let font = font_chain(&TextFont::default());
+        let text = "  indented   by  hand\n\tand tabbed";
+        let auto = layout_text(font, text, 18.0, None, TextAlign::Left, 1.0);
+        let fixed = layout_text(
+            font,
+            text,
+            18.0,
+            Some(auto.width + 1.0),
+            TextAlign::Left,
+            1.0,
+        );
+        let texts = |layout: &super::TextLayout| -> Vec<String> {
+            layout.lines.iter().map(|line| line.text.clone()).collect()
+        };
+        assert_eq!(texts(&fixed), texts(&auto));
+
+        let narrow = layout_text(font, "one   two", 18.0, Some(45.0), TextAlign::Left, 1.0);
+        assert_eq!(texts(&narrow), vec!["one", "two"]);
+    }
+}
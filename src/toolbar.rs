@@ -1,8 +1,14 @@
 use egui::{
-    vec2, Align, Align2, Color32, ComboBox, FontId, Layout, Pos2, Rect, RichText, Shape, Stroke, Ui,
+    vec2, Align, Align2, Color32, ComboBox, FontId, Layout, Pos2, Rect, RichText, Shape, Slider,
+    Stroke, Ui,
 };
 
-use crate::annotation::{StrokeWidth, TextSize, Tool};
+use crate::annotation::{
+    ArrowHead, LoupeShape, RedactMode, SpotlightShape, StampIcon, StrokeStyle, StrokeWidth,
+    TextAlign, TextEffects, TextSize, Tool, Turn,
+};
+use crate::canvas::paint_stamp;
+use crate::fonts;
 use crate::state::EditorState;
 use crate::theme::{self, WidthClass};
 use crate::ui_controls;
@@ -18,35 +24,123 @@ const PALETTE: [[u8; 4]; 8] = [
     [0x1A, 0x20, 0x2C, 0xFF],
 ];
 
+// Highlighter colours are multiplied onto the image, so they stay light enough
+// for dark text underneath to remain readable.
+const HIGHLIGHTER_PALETTE: [[u8; 4]; 6] = [
+    [0xFF, 0xE2, 0x3D, 0xFF],
+    [0x9B, 0xF0, 0x7A, 0xFF],
+    [0x7F, 0xE3, 0xF5, 0xFF],
+    [0xFF, 0x9E, 0xD2, 0xFF],
+    [0xFF, 0xC0, 0x7A, 0xFF],
+    [0xC8, 0xB2, 0xFF, 0xFF],
+];
+
+fn active_palette(state: &EditorState) -> &'static [[u8; 4]] {
+    if state.uses_highlight_palette() {
+        &HIGHLIGHTER_PALETTE
+    } else {
+        &PALETTE
+    }
+}
+
+fn palette_selected(state: &EditorState, color: [u8; 4]) -> bool {
+    if state.uses_highlight_palette() {
+        state.active_highlight_color == color
+    } else {
+        state.active_color == color
+    }
+}
+
+fn pick_palette_color(state: &mut EditorState, color: [u8; 4]) {
+    if state.uses_highlight_palette() {
+        state.set_highlight_color(color);
+    } else {
+        state.set_color(color);
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct ToolbarPlan {
     pub show_tools_inline: bool,
     pub visible_color_count: usize,
     pub show_stroke_inline: bool,
     pub show_text_size_inline: bool,
+    pub show_redact_mode_inline: bool,
+    pub show_arrow_head_inline: bool,
+    pub show_polygon_fill_inline: bool,
+    pub show_spotlight_inline: bool,
+    pub show_loupe_inline: bool,
+    pub show_stamp_inline: bool,
+    pub show_fill_inline: bool,
+    pub show_text_font_inline: bool,
+    pub show_text_effects_inline: bool,
+    pub show_text_layout_inline: bool,
     pub show_overflow: bool,
 }
 
 pub fn plan_toolbar_items(width_class: WidthClass, state: &EditorState) -> ToolbarPlan {
-    let needs_text_size =
-        state.active_tool == Tool::Text || state.active_tool == Tool::ArrowWithText;
+    let needs_text_size = matches!(
+        state.active_tool,
+        Tool::Text | Tool::ArrowWithText | Tool::Step | Tool::Callout
+    );
+    let palette_len = active_palette(state).len();
     let visible_color_count = match width_class {
         WidthClass::Compact => 4,
         WidthClass::Regular => 6,
-        WidthClass::Wide => PALETTE.len(),
-    };
+        WidthClass::Wide => palette_len,
+    }
+    .min(palette_len);
     let show_stroke_inline = width_class != WidthClass::Compact;
     let show_text_size_inline = needs_text_size && width_class != WidthClass::Compact;
+    let needs_redact_mode = state.uses_redact_controls();
+    let show_redact_mode_inline = needs_redact_mode && width_class != WidthClass::Compact;
+    let needs_arrow_head = state.uses_arrow_head_controls();
+    let show_arrow_head_inline = needs_arrow_head && width_class == WidthClass::Wide;
+    let needs_polygon_fill = state.uses_polygon_fill_controls();
+    let show_polygon_fill_inline = needs_polygon_fill && width_class != WidthClass::Compact;
+    let needs_spotlight = state.uses_spotlight_controls();
+    let show_spotlight_inline = needs_spotlight && width_class == WidthClass::Wide;
+    let needs_loupe = state.uses_loupe_controls();
+    let show_loupe_inline = needs_loupe && width_class != WidthClass::Compact;
+    let needs_stamp = state.uses_stamp_controls();
+    let show_stamp_inline = needs_stamp && width_class != WidthClass::Compact;
+    let needs_fill = state.uses_fill_controls();
+    let show_fill_inline = needs_fill && width_class == WidthClass::Wide;
+    let needs_text_effects = state.uses_text_effects_controls();
+    let show_text_font_inline = needs_text_effects && width_class != WidthClass::Compact;
+    let show_text_effects_inline = needs_text_effects && width_class == WidthClass::Wide;
+    let needs_text_layout = state.uses_text_layout_controls();
+    let show_text_layout_inline = needs_text_layout && width_class == WidthClass::Wide;
 
-    let hidden_for_overflow = visible_color_count < PALETTE.len()
+    let hidden_for_overflow = visible_color_count < palette_len
         || !show_stroke_inline
-        || (needs_text_size && !show_text_size_inline);
+        || (needs_text_size && !show_text_size_inline)
+        || (needs_redact_mode && !show_redact_mode_inline)
+        || (needs_arrow_head && !show_arrow_head_inline)
+        || (needs_polygon_fill && !show_polygon_fill_inline)
+        || (needs_spotlight && !show_spotlight_inline)
+        || (needs_loupe && !show_loupe_inline)
+        || (needs_stamp && !show_stamp_inline)
+        || (needs_fill && !show_fill_inline)
+        || (needs_text_effects && !show_text_font_inline)
+        || (needs_text_effects && !show_text_effects_inline)
+        || (needs_text_layout && !show_text_layout_inline);
 
     ToolbarPlan {
         show_tools_inline: true,
         visible_color_count,
         show_stroke_inline,
         show_text_size_inline,
+        show_redact_mode_inline,
+        show_arrow_head_inline,
+        show_polygon_fill_inline,
+        show_spotlight_inline,
+        show_loupe_inline,
+        show_stamp_inline,
+        show_fill_inline,
+        show_text_font_inline,
+        show_text_effects_inline,
+        show_text_layout_inline,
         show_overflow: hidden_for_overflow,
     }
 }
@@ -76,9 +170,11 @@ pub fn show_toolbar(ui: &mut Ui, state: &mut EditorState, width_class: WidthClas
                     .color(theme.text.muted)
                     .size(12.0),
             );
-            stroke_button(ui, state, StrokeWidth::Thin, "S");
-            stroke_button(ui, state, StrokeWidth::Medium, "M");
-            stroke_button(ui, state, StrokeWidth::Thick, "L");
+            stroke_width_slider(ui, state);
+            if state.uses_stroke_style_controls() {
+                ui.add_space(theme.layout.space_1);
+                stroke_style_buttons(ui, state);
+            }
         }
 
         if plan.show_text_size_inline {
@@ -86,27 +182,78 @@ pub fn show_toolbar(ui: &mut Ui, state: &mut EditorState, width_class: WidthClas
             text_size_points_control(ui, state, "toolbar_text_size_inline");
         }
 
+        if plan.show_redact_mode_inline {
+            group_separator(ui, &theme);
+            redact_mode_buttons(ui, state);
+        }
+
+        if plan.show_arrow_head_inline {
+            group_separator(ui, &theme);
+            arrow_head_buttons(ui, state);
+        }
+
+        if plan.show_polygon_fill_inline {
+            group_separator(ui, &theme);
+            polygon_fill_buttons(ui, state);
+        }
+
+        if plan.show_spotlight_inline {
+            group_separator(ui, &theme);
+            spotlight_controls(ui, state, "toolbar_spotlight_inline");
+        }
+
+        if plan.show_loupe_inline {
+            group_separator(ui, &theme);
+            loupe_buttons(ui, state);
+        }
+
+        if plan.show_stamp_inline {
+            group_separator(ui, &theme);
+            stamp_buttons(ui, state);
+        }
+
+        if plan.show_fill_inline {
+            group_separator(ui, &theme);
+            fill_controls(ui, state, "toolbar_fill_inline");
+        }
+
+        if plan.show_text_font_inline {
+            group_separator(ui, &theme);
+            text_font_controls(ui, state, "toolbar_text_font_inline");
+        }
+
+        if plan.show_text_effects_inline {
+            group_separator(ui, &theme);
+            text_effects_controls(ui, state, "toolbar_text_effects_inline");
+        }
+
+        if plan.show_text_layout_inline {
+            group_separator(ui, &theme);
+            text_layout_controls(ui, state, "toolbar_text_layout_inline");
+        }
+
         ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
             if plan.show_overflow {
                 ui.menu_button("…", |ui| {
                     ui.spacing_mut().item_spacing =
                         vec2(theme.layout.control_gap, theme.layout.space_2);
 
-                    if plan.visible_color_count < PALETTE.len() {
+                    let palette = active_palette(state);
+                    if plan.visible_color_count < palette.len() {
                         ui.label(RichText::new("Colors").color(theme.text.muted).size(12.0));
                         ui.horizontal_wrapped(|ui| {
                             ui.spacing_mut().item_spacing =
                                 vec2(theme.layout.control_gap, theme.layout.space_1);
-                            for color in PALETTE.iter().skip(plan.visible_color_count) {
+                            for color in palette.iter().skip(plan.visible_color_count) {
                                 let color32 = Color32::from_rgba_unmultiplied(
                                     color[0], color[1], color[2], color[3],
                                 );
-                                let selected = state.active_color == *color;
+                                let selected = palette_selected(state, *color);
                                 if ui_controls::color_chip(ui, &theme, color32, selected)
                                     .on_hover_text("Choose color")
                                     .clicked()
                                 {
-                                    state.set_color(*color);
+                                    pick_palette_color(state, *color);
                                     ui.close_menu();
                                 }
                             }
@@ -120,15 +267,16 @@ pub fn show_toolbar(ui: &mut Ui, state: &mut EditorState, width_class: WidthClas
                                 .color(theme.text.muted)
                                 .size(12.0),
                         );
-                        ui.horizontal(|ui| {
-                            stroke_button(ui, state, StrokeWidth::Thin, "S");
-                            stroke_button(ui, state, StrokeWidth::Medium, "M");
-                            stroke_button(ui, state, StrokeWidth::Thick, "L");
-                        });
+                        ui.horizontal(|ui| stroke_width_slider(ui, state));
+                        if state.uses_stroke_style_controls() {
+                            ui.horizontal(|ui| stroke_style_buttons(ui, state));
+                        }
                     }
 
-                    let needs_text_size =
-                        state.active_tool == Tool::Text || state.active_tool == Tool::ArrowWithText;
+                    let needs_text_size = matches!(
+                        state.active_tool,
+                        Tool::Text | Tool::ArrowWithText | Tool::Step | Tool::Callout
+                    );
                     if needs_text_size && !plan.show_text_size_inline {
                         ui.separator();
                         ui.label(
@@ -140,6 +288,102 @@ pub fn show_toolbar(ui: &mut Ui, state: &mut EditorState, width_class: WidthClas
                             text_size_points_control(ui, state, "toolbar_text_size_overflow");
                         });
                     }
+
+                    if state.uses_arrow_head_controls() && !plan.show_arrow_head_inline {
+                        ui.separator();
+                        ui.label(RichText::new("Line end").color(theme.text.muted).size(12.0));
+                        ui.horizontal(|ui| {
+                            arrow_head_buttons(ui, state);
+                        });
+                    }
+
+                    if state.uses_redact_controls() && !plan.show_redact_mode_inline {
+                        ui.separator();
+                        ui.label(
+                            RichText::new("Redaction")
+                                .color(theme.text.muted)
+                                .size(12.0),
+                        );
+                        ui.horizontal(|ui| {
+                            redact_mode_buttons(ui, state);
+                        });
+                    }
+
+                    if state.uses_polygon_fill_controls() && !plan.show_polygon_fill_inline {
+                        ui.separator();
+                        ui.label(RichText::new("Polygon").color(theme.text.muted).size(12.0));
+                        ui.horizontal(|ui| {
+                            polygon_fill_buttons(ui, state);
+                        });
+                    }
+
+                    if state.uses_spotlight_controls() && !plan.show_spotlight_inline {
+                        ui.separator();
+                        ui.label(
+                            RichText::new("Spotlight")
+                                .color(theme.text.muted)
+                                .size(12.0),
+                        );
+                        ui.horizontal(|ui| {
+                            spotlight_controls(ui, state, "toolbar_spotlight_overflow");
+                        });
+                    }
+
+                    if state.uses_loupe_controls() && !plan.show_loupe_inline {
+                        ui.separator();
+                        ui.label(RichText::new("Loupe").color(theme.text.muted).size(12.0));
+                        ui.horizontal(|ui| {
+                            loupe_buttons(ui, state);
+                        });
+                    }
+
+                    if state.uses_stamp_controls() && !plan.show_stamp_inline {
+                        ui.separator();
+                        ui.label(RichText::new("Stamp").color(theme.text.muted).size(12.0));
+                        ui.horizontal_wrapped(|ui| {
+                            stamp_buttons(ui, state);
+                        });
+                    }
+
+                    if state.uses_fill_controls() && !plan.show_fill_inline {
+                        ui.separator();
+                        ui.label(RichText::new("Fill").color(theme.text.muted).size(12.0));
+                        ui.horizontal(|ui| {
+                            fill_controls(ui, state, "toolbar_fill_overflow");
+                        });
+                    }
+
+                    if state.uses_text_effects_controls() && !plan.show_text_font_inline {
+                        ui.separator();
+                        ui.label(RichText::new("Font").color(theme.text.muted).size(12.0));
+                        ui.horizontal(|ui| {
+                            text_font_controls(ui, state, "toolbar_text_font_overflow");
+                        });
+                    }
+
+                    if state.uses_text_effects_controls() && !plan.show_text_effects_inline {
+                        ui.separator();
+                        ui.label(
+                            RichText::new("Text effects")
+                                .color(theme.text.muted)
+                                .size(12.0),
+                        );
+                        ui.horizontal(|ui| {
+                            text_effects_controls(ui, state, "toolbar_text_effects_overflow");
+                        });
+                    }
+
+                    if state.uses_text_layout_controls() && !plan.show_text_layout_inline {
+                        ui.separator();
+                        ui.label(
+                            RichText::new("Paragraph")
+                                .color(theme.text.muted)
+                                .size(12.0),
+                        );
+                        ui.horizontal(|ui| {
+                            text_layout_controls(ui, state, "toolbar_text_layout_overflow");
+                        });
+                    }
                 });
             }
         });
@@ -153,6 +397,20 @@ fn render_tool_group(ui: &mut Ui, state: &mut EditorState) {
     tool_button(ui, state, Tool::Text, "Text (T)");
     tool_button(ui, state, Tool::Rectangle, "Rectangle (R)");
     tool_button(ui, state, Tool::Ellipse, "Ellipse (E)");
+    tool_button(ui, state, Tool::Freehand, "Pen (P)");
+    tool_button(ui, state, Tool::Highlighter, "Highlighter (H)");
+    tool_button(ui, state, Tool::Redact, "Redact (B)");
+    tool_button(ui, state, Tool::Step, "Numbered step (N)");
+    tool_button(ui, state, Tool::Callout, "Callout (C)");
+    tool_button(ui, state, Tool::Stamp, "Stamp (K)");
+    tool_button(ui, state, Tool::Spotlight, "Spotlight (S)");
+    tool_button(ui, state, Tool::Magnifier, "Magnifier (M)");
+    tool_button(
+        ui,
+        state,
+        Tool::Polygon,
+        "Polygon (L) — click vertices, double-click or Enter to close, Shift to leave open",
+    );
 }
 
 fn render_palette_group(
@@ -161,14 +419,14 @@ fn render_palette_group(
     theme: &theme::AppTheme,
     count: usize,
 ) {
-    for color in PALETTE.iter().take(count) {
+    for color in active_palette(state).iter().take(count) {
         let color32 = Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3]);
-        let selected = state.active_color == *color;
+        let selected = palette_selected(state, *color);
         if ui_controls::color_chip(ui, theme, color32, selected)
             .on_hover_text("Choose color")
             .clicked()
         {
-            state.set_color(*color);
+            pick_palette_color(state, *color);
         }
     }
 }
@@ -283,21 +541,656 @@ fn draw_tool_icon(ui: &Ui, rect: Rect, tool: Tool, selected: bool) {
                 color,
             );
         }
+        Tool::Freehand => {
+            let left = icon_rect.left() + 2.0;
+            let width = icon_rect.width() - 4.0;
+            let mid = icon_rect.center().y;
+            let points: Vec<Pos2> = (0..=16)
+                .map(|step| {
+                    let t = step as f32 / 16.0;
+                    Pos2::new(
+                        left + width * t,
+                        mid + (t * std::f32::consts::TAU * 1.25).sin() * 3.5,
+                    )
+                })
+                .collect();
+            painter.add(Shape::line(points, stroke));
+        }
+        Tool::Magnifier => {
+            let lens = Rect::from_center_size(
+                icon_rect.center() - vec2(2.0, 2.0),
+                vec2(icon_rect.height(), icon_rect.height()) * 0.72,
+            );
+            painter.circle_stroke(lens.center(), lens.width() * 0.5, stroke);
+            painter.line_segment(
+                [
+                    lens.center() + vec2(lens.width(), lens.width()) * 0.36,
+                    icon_rect.right_bottom() - vec2(1.0, 0.0),
+                ],
+                Stroke::new(2.4, color),
+            );
+        }
+        Tool::Spotlight => {
+            let r = icon_rect.shrink2(vec2(0.5, 0.0));
+            painter.rect_filled(
+                r,
+                2.0,
+                Color32::from_rgba_unmultiplied(color.r(), color.g(), color.b(), 70),
+            );
+            painter.circle_filled(r.center(), r.height() * 0.32, theme.surfaces.panel_bg);
+            painter.circle_stroke(r.center(), r.height() * 0.32, stroke);
+        }
+        Tool::Polygon => {
+            let r = icon_rect.shrink2(vec2(1.5, 1.0));
+            let points = vec![
+                Pos2::new(r.left(), r.top() + 2.0),
+                Pos2::new(r.center().x + 1.0, r.top()),
+                Pos2::new(r.right(), r.center().y),
+                Pos2::new(r.center().x, r.center().y + 1.0),
+                Pos2::new(r.left() + 3.0, r.bottom()),
+            ];
+            for point in &points {
+                painter.circle_filled(*point, 1.6, color);
+            }
+            painter.add(Shape::closed_line(points, stroke));
+        }
+        Tool::Redact => {
+            let r = icon_rect.shrink2(vec2(2.0, 3.0));
+            let cell = r.width() / 4.0;
+            for row in 0..3 {
+                for col in 0..4 {
+                    if (row + col) % 2 == 0 {
+                        let min = Pos2::new(
+                            r.left() + col as f32 * cell,
+                            r.top() + row as f32 * r.height() / 3.0,
+                        );
+                        painter.rect_filled(
+                            Rect::from_min_size(min, vec2(cell, r.height() / 3.0)),
+                            0.0,
+                            Color32::from_rgba_unmultiplied(color.r(), color.g(), color.b(), 110),
+                        );
+                    }
+                }
+            }
+            painter.rect_stroke(r, 2.0, stroke);
+        }
+        Tool::Callout => {
+            let bubble = Rect::from_min_max(
+                icon_rect.left_top() + vec2(1.0, 1.0),
+                icon_rect.right_bottom() - vec2(1.0, 5.0),
+            );
+            painter.rect_stroke(bubble, 3.0, stroke);
+            painter.line_segment(
+                [
+                    Pos2::new(bubble.left() + 4.0, bubble.bottom()),
+                    Pos2::new(bubble.left() + 3.0, icon_rect.bottom()),
+                ],
+                stroke,
+            );
+            painter.line_segment(
+                [
+                    Pos2::new(bubble.left() + 3.0, icon_rect.bottom()),
+                    Pos2::new(bubble.left() + 8.0, bubble.bottom()),
+                ],
+                stroke,
+            );
+        }
+        Tool::Step => {
+            let radius = icon_rect.width().min(icon_rect.height()) * 0.46;
+            painter.circle_filled(icon_rect.center(), radius, color);
+            painter.text(
+                icon_rect.center(),
+                Align2::CENTER_CENTER,
+                "1",
+                FontId::proportional(11.0),
+                theme.surfaces.panel_bg,
+            );
+        }
+        Tool::Stamp => {
+            let square = Rect::from_center_size(icon_rect.center(), vec2(16.0, 16.0));
+            paint_stamp(
+                painter,
+                StampIcon::Check,
+                square,
+                Turn::NONE,
+                color,
+                theme.surfaces.panel_bg,
+            );
+        }
+        Tool::Highlighter => {
+            let band = Rect::from_center_size(
+                icon_rect.center() + vec2(0.0, 2.0),
+                vec2(icon_rect.width() - 4.0, 6.0),
+            );
+            painter.rect_filled(
+                band,
+                1.5,
+                Color32::from_rgba_unmultiplied(color.r(), color.g(), color.b(), 70),
+            );
+            painter.line_segment(
+                [
+                    Pos2::new(band.left() + 3.0, band.top() - 3.0),
+                    Pos2::new(band.right() - 3.0, band.top() - 3.0),
+                ],
+                stroke,
+            );
+        }
+    }
+}
+
+fn stroke_width_slider(ui: &mut Ui, state: &mut EditorState) {
+    let mut px = state.active_stroke.px();
+    ui.spacing_mut().slider_width = 96.0;
+    let response = ui
+        .add(
+            Slider::new(&mut px, StrokeWidth::MIN..=StrokeWidth::MAX)
+                .logarithmic(true)
+                .max_decimals(1)
+                .suffix(" px"),
+        )
+        .on_hover_text("Line thickness");
+    if response.changed() {
+        // Dragging restyles live; the undo step is recorded on release.
+        if response.dragged() {
+            state.preview_stroke(StrokeWidth::from_px(px));
+        } else {
+            state.set_stroke(StrokeWidth::from_px(px));
+        }
+    }
+    if response.drag_stopped() {
+        state.commit_stroke();
+    }
+}
+
+fn stroke_style_buttons(ui: &mut Ui, state: &mut EditorState) {
+    let theme = theme::premium_dark_theme();
+    for (style, hint) in [
+        (StrokeStyle::Solid, "Line style: solid"),
+        (StrokeStyle::Dashed, "Line style: dashed"),
+        (StrokeStyle::Dotted, "Line style: dotted"),
+    ] {
+        let selected = state.active_stroke_style == style;
+        let response = ui_controls::segmented(ui, &theme, "", selected).on_hover_text(hint);
+        draw_stroke_style_icon(ui, response.rect, style, selected);
+        if response.clicked() {
+            state.set_stroke_style(style);
+        }
     }
 }
 
-fn stroke_button(ui: &mut Ui, state: &mut EditorState, stroke: StrokeWidth, label: &str) {
+fn draw_stroke_style_icon(ui: &Ui, rect: Rect, style: StrokeStyle, selected: bool) {
     let theme = theme::premium_dark_theme();
-    let hint = match stroke {
-        StrokeWidth::Thin => "Line thickness: Small",
-        StrokeWidth::Medium => "Line thickness: Medium",
-        StrokeWidth::Thick => "Line thickness: Large",
+    let color = if selected {
+        theme.text.primary
+    } else {
+        theme.text.secondary
     };
-    if ui_controls::segmented(ui, &theme, label, state.active_stroke == stroke)
-        .on_hover_text(hint)
+    let stroke = Stroke::new(1.5, color);
+    let icon_rect = rect.shrink2(vec2(7.0, 5.0));
+    let y = icon_rect.center().y;
+    let line = [
+        Pos2::new(icon_rect.left(), y),
+        Pos2::new(icon_rect.right(), y),
+    ];
+    match style.dash_pattern(stroke.width) {
+        None => {
+            ui.painter().line_segment(line, stroke);
+        }
+        Some([on, off]) if style == StrokeStyle::Dotted => {
+            ui.painter().extend(Shape::dotted_line(
+                &line,
+                color,
+                on + off,
+                stroke.width * 0.5,
+            ));
+        }
+        Some([on, off]) => {
+            ui.painter()
+                .extend(Shape::dashed_line(&line, stroke, on, off));
+        }
+    }
+}
+
+fn redact_mode_buttons(ui: &mut Ui, state: &mut EditorState) {
+    let theme = theme::premium_dark_theme();
+    for (mode, label, hint) in [
+        (RedactMode::Blur, "Blur", "Redact with gaussian blur"),
+        (
+            RedactMode::Pixelate,
+            "Pixelate",
+            "Redact with large pixel blocks",
+        ),
+        (
+            RedactMode::Solid,
+            "Solid",
+            "Redact with a solid fill in the active color",
+        ),
+    ] {
+        if ui_controls::segmented(ui, &theme, label, state.active_redact_mode == mode)
+            .on_hover_text(hint)
+            .clicked()
+        {
+            state.set_redact_mode(mode);
+        }
+    }
+}
+
+fn stamp_buttons(ui: &mut Ui, state: &mut EditorState) {
+    let theme = theme::premium_dark_theme();
+    for icon in StampIcon::ALL {
+        let selected = state.active_stamp == icon;
+        let response = ui_controls::segmented(ui, &theme, "", selected).on_hover_text(icon.label());
+        let color = if selected {
+            theme.text.primary
+        } else {
+            theme.text.secondary
+        };
+        let square = Rect::from_center_size(response.rect.center(), vec2(16.0, 16.0));
+        paint_stamp(
+            ui.painter(),
+            icon,
+            square,
+            Turn::NONE,
+            color,
+            theme.surfaces.panel_bg,
+        );
+        if response.clicked() {
+            state.set_stamp_icon(icon);
+        }
+    }
+}
+
+fn polygon_fill_buttons(ui: &mut Ui, state: &mut EditorState) {
+    let theme = theme::premium_dark_theme();
+    for (filled, label, hint) in [
+        (false, "Outline", "Polygon: outline only"),
+        (
+            true,
+            "Filled",
+            "Polygon: translucent fill in the active color",
+        ),
+    ] {
+        if ui_controls::segmented(ui, &theme, label, state.active_polygon_fill == filled)
+            .on_hover_text(hint)
+            .clicked()
+        {
+            state.set_polygon_fill(filled);
+        }
+    }
+}
+
+fn fill_controls(ui: &mut Ui, state: &mut EditorState, id_suffix: &'static str) {
+    let theme = theme::premium_dark_theme();
+    let enabled = state.active_fill_enabled;
+    if ui_controls::segmented(ui, &theme, "Fill", enabled)
+        .on_hover_text("Fill rectangles and ellipses")
         .clicked()
     {
-        state.set_stroke(stroke);
+        state.set_fill_enabled(!enabled);
+    }
+
+    let [r, g, b, _] = state.active_fill_color;
+    let swatch = ui_controls::color_chip(ui, &theme, Color32::from_rgb(r, g, b), false)
+        .on_hover_text("Fill color");
+    let popup_id = ui.make_persistent_id(("snapmark_fill_palette", id_suffix));
+    if swatch.clicked() {
+        ui.memory_mut(|memory| memory.toggle_popup(popup_id));
+    }
+    egui::popup_below_widget(ui, popup_id, &swatch, |ui| {
+        ui.horizontal(|ui| {
+            for color in PALETTE {
+                let selected = state.active_fill_color[..3] == color[..3];
+                let color32 = Color32::from_rgb(color[0], color[1], color[2]);
+                if ui_controls::color_chip(ui, &theme, color32, selected).clicked() {
+                    state.set_fill_color(color);
+                }
+            }
+        });
+    });
+
+    let current = (state.active_fill_color[3] as f32 / 255.0 * 100.0).round() as u32;
+    let mut percent = current;
+    ui.scope(|ui| {
+        ui.spacing_mut().interact_size.y = theme.layout.chip_h;
+        ui.spacing_mut().button_padding.y = theme.layout.space_1;
+        ComboBox::from_id_source(("snapmark_toolbar_fill_opacity", id_suffix))
+            .selected_text(format!("{percent}%"))
+            .width(64.0)
+            .show_ui(ui, |ui| {
+                for option in (10..=100).step_by(10) {
+                    ui.selectable_value(&mut percent, option, format!("{option}%"));
+                }
+            });
+    })
+    .response
+    .on_hover_text("Fill opacity");
+    if percent != current {
+        state.set_fill_opacity((percent as f32 / 100.0 * 255.0).round() as u8);
+    }
+}
+
+fn text_font_controls(ui: &mut Ui, state: &mut EditorState, id_suffix: &'static str) {
+    let theme = theme::premium_dark_theme();
+    let mut font = state.active_text_font.clone();
+    let family = fonts::family(&font.family);
+
+    ui.scope(|ui| {
+        ui.spacing_mut().interact_size.y = theme.layout.chip_h;
+        ui.spacing_mut().button_padding.y = theme.layout.space_1;
+        ComboBox::from_id_source(("snapmark_toolbar_font_family", id_suffix))
+            .selected_text(family.name.as_str())
+            .width(120.0)
+            .show_ui(ui, |ui| {
+                for option in fonts::families() {
+                    ui.selectable_value(&mut font.family, option.name.clone(), &option.name);
+                }
+            });
+    })
+    .response
+    .on_hover_text("Font family");
+
+    // Styles the family does not ship are disabled rather than faked, so the
+    // canvas and the export always use the same face.
+    let family = fonts::family(&font.family);
+    let bold_available = family.has_style(!font.bold, font.italic);
+    ui.add_enabled_ui(bold_available || font.bold, |ui| {
+        if ui_controls::segmented(ui, &theme, "B", font.bold)
+            .on_hover_text("Bold")
+            .on_disabled_hover_text("This font has no bold face")
+            .clicked()
+        {
+            font.bold = !font.bold;
+        }
+    });
+    let italic_available = family.has_style(font.bold, !font.italic);
+    ui.add_enabled_ui(italic_available || font.italic, |ui| {
+        if ui_controls::segmented(ui, &theme, "I", font.italic)
+            .on_hover_text("Italic")
+            .on_disabled_hover_text("This font has no italic face")
+            .clicked()
+        {
+            font.italic = !font.italic;
+        }
+    });
+
+    if font != state.active_text_font {
+        state.set_text_font(font);
+    }
+}
+
+fn text_effects_controls(ui: &mut Ui, state: &mut EditorState, id_suffix: &'static str) {
+    let theme = theme::premium_dark_theme();
+    let mut effects = state.active_text_effects;
+
+    if ui_controls::segmented(ui, &theme, "Halo", effects.outline)
+        .on_hover_text("Outline each letter so text reads on any background")
+        .clicked()
+    {
+        effects.outline = !effects.outline;
+    }
+    if let Some(color) = effect_color_chip(
+        ui,
+        effects.outline_color,
+        "Halo color",
+        ("snapmark_halo_palette", id_suffix),
+    ) {
+        effects.outline_color = color;
+        effects.outline = true;
+    }
+    let current = effects.outline_width.round() as u32;
+    let mut width = current;
+    ui.scope(|ui| {
+        ui.spacing_mut().interact_size.y = theme.layout.chip_h;
+        ui.spacing_mut().button_padding.y = theme.layout.space_1;
+        ComboBox::from_id_source(("snapmark_toolbar_halo_width", id_suffix))
+            .selected_text(format!("{width} px"))
+            .width(56.0)
+            .show_ui(ui, |ui| {
+                for option in 1..=TextEffects::MAX_OUTLINE_WIDTH as u32 {
+                    ui.selectable_value(&mut width, option, format!("{option} px"));
+                }
+            });
+    })
+    .response
+    .on_hover_text("Halo width");
+    if width != current {
+        effects.outline_width = width as f32;
+        effects.outline = true;
+    }
+
+    if ui_controls::segmented(ui, &theme, "Shadow", effects.shadow)
+        .on_hover_text("Drop shadow behind the text")
+        .clicked()
+    {
+        effects.shadow = !effects.shadow;
+    }
+
+    if ui_controls::segmented(ui, &theme, "Plate", effects.background)
+        .on_hover_text("Rounded background plate behind the text")
+        .clicked()
+    {
+        effects.background = !effects.background;
+    }
+    if let Some([r, g, b, _]) = effect_color_chip(
+        ui,
+        effects.background_color,
+        "Plate color",
+        ("snapmark_plate_palette", id_suffix),
+    ) {
+        // The plate keeps its slight translucency.
+        effects.background_color = [r, g, b, effects.background_color[3]];
+        effects.background = true;
+    }
+
+    if effects != state.active_text_effects {
+        state.set_text_effects(effects);
+    }
+}
+
+fn text_layout_controls(ui: &mut Ui, state: &mut EditorState, id_suffix: &'static str) {
+    let theme = theme::premium_dark_theme();
+    for (align, label, hint) in [
+        (TextAlign::Left, "Left", "Align lines to the left"),
+        (TextAlign::Center, "Center", "Center lines in the text box"),
+        (TextAlign::Right, "Right", "Align lines to the right"),
+    ] {
+        if ui_controls::segmented(ui, &theme, label, state.active_text_align == align)
+            .on_hover_text(hint)
+            .clicked()
+        {
+            state.set_text_align(align);
+        }
+    }
+
+    let current = state.active_line_spacing;
+    let mut spacing = current;
+    ui.scope(|ui| {
+        ui.spacing_mut().interact_size.y = theme.layout.chip_h;
+        ui.spacing_mut().button_padding.y = theme.layout.space_1;
+        ComboBox::from_id_source(("snapmark_toolbar_line_spacing", id_suffix))
+            .selected_text(format!("{spacing:.2}×"))
+            .width(64.0)
+            .show_ui(ui, |ui| {
+                for option in [1.0, 1.15, 1.5, 2.0] {
+                    ui.selectable_value(&mut spacing, option, format!("{option:.2}×"));
+                }
+            });
+    })
+    .response
+    .on_hover_text("Line spacing");
+    if spacing != current {
+        state.set_line_spacing(spacing);
+    }
+
+    if let Some(auto) = state.selected_text_auto_width() {
+        if ui_controls::segmented(ui, &theme, "Auto width", auto)
+            .on_hover_text("Size the box to the longest line, or drag its width handle to wrap")
+            .clicked()
+        {
+            state.set_text_auto_width(!auto);
+        }
+    }
+}
+
+/// Colour swatch that opens the palette; returns the colour picked this frame.
+fn effect_color_chip(
+    ui: &mut Ui,
+    current: [u8; 4],
+    hint: &str,
+    popup_key: (&'static str, &'static str),
+) -> Option<[u8; 4]> {
+    let theme = theme::premium_dark_theme();
+    let [r, g, b, _] = current;
+    let swatch =
+        ui_controls::color_chip(ui, &theme, Color32::from_rgb(r, g, b), false).on_hover_text(hint);
+    let popup_id = ui.make_persistent_id(popup_key);
+    if swatch.clicked() {
+        ui.memory_mut(|memory| memory.toggle_popup(popup_id));
+    }
+    let mut picked = None;
+    egui::popup_below_widget(ui, popup_id, &swatch, |ui| {
+        ui.horizontal(|ui| {
+            for color in PALETTE {
+                let selected = current[..3] == color[..3];
+                let color32 = Color32::from_rgb(color[0], color[1], color[2]);
+                if ui_controls::color_chip(ui, &theme, color32, selected).clicked() {
+                    picked = Some(color);
+                }
+            }
+        });
+    });
+    picked
+}
+
+fn loupe_buttons(ui: &mut Ui, state: &mut EditorState) {
+    let theme = theme::premium_dark_theme();
+    for (shape, label, hint) in [
+        (LoupeShape::Circle, "Circle", "Round loupe"),
+        (LoupeShape::RoundedRect, "Rect", "Rounded rectangle loupe"),
+    ] {
+        if ui_controls::segmented(ui, &theme, label, state.active_loupe_shape == shape)
+            .on_hover_text(hint)
+            .clicked()
+        {
+            state.set_loupe_shape(shape);
+        }
+    }
+
+    let connector = state.active_loupe_connector;
+    if ui_controls::segmented(ui, &theme, "Line", connector)
+        .on_hover_text("Connect the loupe to the magnified region")
+        .clicked()
+    {
+        state.set_loupe_connector(!connector);
+    }
+}
+
+fn spotlight_controls(ui: &mut Ui, state: &mut EditorState, id_suffix: &'static str) {
+    let theme = theme::premium_dark_theme();
+    for (shape, label, hint) in [
+        (SpotlightShape::Rectangle, "Rect", "Spotlight a rectangle"),
+        (SpotlightShape::Ellipse, "Oval", "Spotlight an ellipse"),
+    ] {
+        if ui_controls::segmented(ui, &theme, label, state.active_spotlight_shape == shape)
+            .on_hover_text(hint)
+            .clicked()
+        {
+            state.set_spotlight_shape(shape);
+        }
+    }
+
+    let mut percent = (state.active_spotlight_dim * 100.0).round() as u32;
+    ui.scope(|ui| {
+        ui.spacing_mut().interact_size.y = theme.layout.chip_h;
+        ui.spacing_mut().button_padding.y = theme.layout.space_1;
+        ComboBox::from_id_source(("snapmark_toolbar_spotlight_dim", id_suffix))
+            .selected_text(format!("{percent}%"))
+            .width(64.0)
+            .show_ui(ui, |ui| {
+                for option in (20..=90).step_by(10) {
+                    ui.selectable_value(&mut percent, option, format!("{option}%"));
+                }
+            });
+    })
+    .response
+    .on_hover_text("How much to darken outside the spotlights; applies to all of them");
+    if percent != (state.active_spotlight_dim * 100.0).round() as u32 {
+        state.set_spotlight_dim(percent as f32 / 100.0);
+    }
+
+    let desaturate = state.active_spotlight_desaturate;
+    if ui_controls::segmented(ui, &theme, "Grey", desaturate)
+        .on_hover_text("Also remove colour outside the spotlights; applies to all of them")
+        .clicked()
+    {
+        state.set_spotlight_desaturate(!desaturate);
+    }
+}
+
+fn arrow_head_buttons(ui: &mut Ui, state: &mut EditorState) {
+    let theme = theme::premium_dark_theme();
+    for (head, hint) in [
+        (ArrowHead::None, "Line end: none (plain line)"),
+        (ArrowHead::Open, "Line end: open chevron"),
+        (ArrowHead::Filled, "Line end: filled triangle"),
+        (ArrowHead::Dot, "Line end: dot"),
+        (ArrowHead::Double, "Line end: arrows on both ends"),
+    ] {
+        let selected = state.active_arrow_head == head;
+        let response = ui_controls::segmented(ui, &theme, "", selected).on_hover_text(hint);
+        draw_arrow_head_icon(ui, response.rect, head, selected);
+        if response.clicked() {
+            state.set_arrow_head(head);
+        }
+    }
+}
+
+fn draw_arrow_head_icon(ui: &Ui, rect: Rect, head: ArrowHead, selected: bool) {
+    let theme = theme::premium_dark_theme();
+    let color = if selected {
+        theme.text.primary
+    } else {
+        theme.text.secondary
+    };
+    let stroke = Stroke::new(1.5, color);
+    let painter = ui.painter();
+    let icon_rect = rect.shrink2(vec2(7.0, 5.0));
+    let y = icon_rect.center().y;
+    let start = Pos2::new(icon_rect.left(), y);
+    let tip = Pos2::new(icon_rect.right(), y);
+    painter.line_segment([start, tip], stroke);
+
+    let filled = |at: Pos2, dir: f32| {
+        painter.add(Shape::convex_polygon(
+            vec![
+                at,
+                Pos2::new(at.x - 5.0 * dir, at.y - 3.5),
+                Pos2::new(at.x - 5.0 * dir, at.y + 3.5),
+            ],
+            color,
+            Stroke::NONE,
+        ));
+    };
+    match head {
+        ArrowHead::None => {}
+        ArrowHead::Open => {
+            painter.add(Shape::line(
+                vec![
+                    Pos2::new(tip.x - 5.0, y - 3.5),
+                    tip,
+                    Pos2::new(tip.x - 5.0, y + 3.5),
+                ],
+                stroke,
+            ));
+        }
+        ArrowHead::Filled => filled(tip, 1.0),
+        ArrowHead::Dot => {
+            painter.circle_filled(tip, 2.8, color);
+        }
+        ArrowHead::Double => {
+            filled(tip, 1.0);
+            filled(start, -1.0);
+        }
     }
 }
 